@@ -0,0 +1,35 @@
+use serde::Serialize;
+
+/// rskafka version this build depends on (keep in sync with Cargo.toml)
+const RSKAFKA_VERSION: &str = "0.6";
+
+/// What this build was compiled with, for support requests
+#[derive(Debug, Clone, Serialize)]
+pub struct AppInfo {
+    pub app_version: String,
+    pub rskafka_version: String,
+    pub tls_backend: String,
+    /// Whether `ssl_skip_verification` is honoured (the `dangerous-tls` feature)
+    pub dangerous_tls_enabled: bool,
+    pub compression_codecs: Vec<String>,
+    pub sasl_mechanisms: Vec<String>,
+}
+
+impl AppInfo {
+    /// Assemble the info from compile-time constants and enabled features
+    pub fn current() -> Self {
+        Self {
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            rskafka_version: RSKAFKA_VERSION.to_string(),
+            tls_backend: "rustls".to_string(),
+            dangerous_tls_enabled: cfg!(feature = "dangerous-tls"),
+            // rskafka's default features compile in every codec
+            compression_codecs: ["NoCompression", "Gzip", "Snappy", "Lz4", "Zstd"]
+                .map(String::from)
+                .to_vec(),
+            sasl_mechanisms: ["Plain", "ScramSha256", "ScramSha512"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::kafka::{
+    build_send_record, decode_records, resolve_compression, topic_visible, ConsumeOptions,
+    ConsumedMessage, DecodePolicy, KafkaError, KafkaService, PartialResult, SendOptions,
+    SendResult, TopicCreateResult,
+};
+use crate::memory::InMemoryBackend;
+
+/// Boxed future returned by `KafkaBackend` methods
+pub type BackendFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, KafkaError>> + Send + 'a>>;
+
+/// The core produce/consume operations, implemented by the rskafka-backed `KafkaService` and by
+/// `InMemoryBackend` for running without a broker.
+///
+/// The matching Tauri commands call through this trait. `AppState` still holds the concrete
+/// `KafkaService`: most other commands use features (caching, offline queue, circuit breaker,
+/// admin calls) that only it has, and it already delegates to the in-memory backend when
+/// `in_memory` is set. Code that only needs these operations should take a `&dyn KafkaBackend`
+/// so it can run against either.
+pub trait KafkaBackend: Send + Sync {
+    fn test_connection(&self, timeout_secs: u64) -> BackendFuture<'_, bool>;
+
+    fn send_message(
+        &self,
+        message: String,
+        key: Option<String>,
+        options: SendOptions,
+    ) -> BackendFuture<'_, SendResult>;
+
+    fn create_topic(
+        &self,
+        topic_name: String,
+        num_partitions: i32,
+        replication_factor: i16,
+    ) -> BackendFuture<'_, TopicCreateResult>;
+
+    fn list_topics(
+        &self,
+        name_filter: Option<String>,
+        include_internal: bool,
+    ) -> BackendFuture<'_, PartialResult<Vec<String>>>;
+
+    fn consume_messages(
+        &self,
+        topic: String,
+        offset: i64,
+        max_messages: i32,
+        dedup_by_key: bool,
+        decode_policy: DecodePolicy,
+        options: ConsumeOptions,
+    ) -> BackendFuture<'_, Vec<ConsumedMessage>>;
+}
+
+impl KafkaBackend for KafkaService {
+    fn test_connection(&self, timeout_secs: u64) -> BackendFuture<'_, bool> {
+        Box::pin(KafkaService::test_connection(self, timeout_secs))
+    }
+
+    fn send_message(
+        &self,
+        message: String,
+        key: Option<String>,
+        options: SendOptions,
+    ) -> BackendFuture<'_, SendResult> {
+        Box::pin(KafkaService::send_message(self, message, key, options))
+    }
+
+    fn create_topic(
+        &self,
+        topic_name: String,
+        num_partitions: i32,
+        replication_factor: i16,
+    ) -> BackendFuture<'_, TopicCreateResult> {
+        Box::pin(KafkaService::create_topic(
+            self,
+            topic_name,
+            num_partitions,
+            replication_factor,
+        ))
+    }
+
+    fn list_topics(
+        &self,
+        name_filter: Option<String>,
+        include_internal: bool,
+    ) -> BackendFuture<'_, PartialResult<Vec<String>>> {
+        Box::pin(KafkaService::list_topics(
+            self,
+            name_filter,
+            include_internal,
+        ))
+    }
+
+    fn consume_messages(
+        &self,
+        topic: String,
+        offset: i64,
+        max_messages: i32,
+        dedup_by_key: bool,
+        decode_policy: DecodePolicy,
+        options: ConsumeOptions,
+    ) -> BackendFuture<'_, Vec<ConsumedMessage>> {
+        Box::pin(KafkaService::consume_messages(
+            self,
+            topic,
+            offset,
+            max_messages,
+            dedup_by_key,
+            decode_policy,
+            options,
+        ))
+    }
+}
+
+impl KafkaBackend for InMemoryBackend {
+    fn test_connection(&self, _timeout_secs: u64) -> BackendFuture<'_, bool> {
+        Box::pin(async { Ok(true) })
+    }
+
+    /// Idempotency keys are stamped but not deduplicated; `KafkaService` tracks acknowledged keys
+    /// itself
+    fn send_message(
+        &self,
+        message: String,
+        key: Option<String>,
+        options: SendOptions,
+    ) -> BackendFuture<'_, SendResult> {
+        Box::pin(async move {
+            let config = self.config().await;
+            let compression =
+                resolve_compression(config.compression_for(&config.topic), options.compression);
+            let partition = options.partition;
+            let (record, idempotency_key) = build_send_record(&config, message, key, options)?;
+            let (partition, offset) = self.store(&config.topic, record, partition).await?;
+            Ok(
+                SendResult::sent("Message stored in memory", compression, idempotency_key)
+                    .at(partition, Some(offset)),
+            )
+        })
+    }
+
+    fn create_topic(
+        &self,
+        topic_name: String,
+        num_partitions: i32,
+        _replication_factor: i16,
+    ) -> BackendFuture<'_, TopicCreateResult> {
+        Box::pin(async move {
+            self.broker()
+                .await
+                .create_topic(&topic_name, num_partitions)?;
+            Ok(TopicCreateResult {
+                success: true,
+                message: format!("Topic '{}' created in memory", topic_name),
+                topic: topic_name,
+            })
+        })
+    }
+
+    fn list_topics(
+        &self,
+        name_filter: Option<String>,
+        include_internal: bool,
+    ) -> BackendFuture<'_, PartialResult<Vec<String>>> {
+        Box::pin(async move {
+            let names = self
+                .broker()
+                .await
+                .topic_metadata()
+                .into_iter()
+                .map(|topic| topic.name)
+                .filter(|name| topic_visible(name, name_filter.as_deref(), include_internal))
+                .collect();
+            Ok(PartialResult {
+                data: names,
+                unreachable_brokers: vec![],
+            })
+        })
+    }
+
+    fn consume_messages(
+        &self,
+        topic: String,
+        offset: i64,
+        max_messages: i32,
+        dedup_by_key: bool,
+        decode_policy: DecodePolicy,
+        options: ConsumeOptions,
+    ) -> BackendFuture<'_, Vec<ConsumedMessage>> {
+        Box::pin(async move {
+            let max_messages = max_messages.max(0) as usize;
+            let partition = options.partition.unwrap_or(0);
+            let fetched = self
+                .fetch_records(
+                    &topic,
+                    partition,
+                    offset,
+                    max_messages,
+                    dedup_by_key,
+                    &options,
+                )
+                .await?;
+            Ok(decode_records(
+                fetched.records,
+                partition,
+                decode_policy,
+                options.value_truncate_bytes,
+            )
+            .messages)
+        })
+    }
+}
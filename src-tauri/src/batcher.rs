@@ -0,0 +1,113 @@
+use rskafka::record::Record;
+use std::time::{Duration, Instant};
+
+/// Groups records into produce batches like the Java producer's `batch.size`/`linger.ms`: a batch
+/// is handed out once it holds `batch_size` records or its oldest record has waited `linger`. A
+/// zero `linger` never flushes early, so batches fill up to `batch_size`.
+pub struct RecordBatcher {
+    batch_size: usize,
+    linger: Duration,
+    pending: Vec<Record>,
+    opened_at: Option<Instant>,
+    batches_flushed: usize,
+}
+
+impl RecordBatcher {
+    pub fn new(batch_size: usize, linger: Duration) -> Self {
+        let batch_size = batch_size.max(1);
+        Self {
+            batch_size,
+            linger,
+            pending: Vec::with_capacity(batch_size),
+            opened_at: None,
+            batches_flushed: 0,
+        }
+    }
+
+    /// Add a record, returning the batch if it is now full or has lingered long enough
+    pub fn push(&mut self, record: Record) -> Option<Vec<Record>> {
+        let opened_at = *self.opened_at.get_or_insert_with(Instant::now);
+        self.pending.push(record);
+
+        let lingered = !self.linger.is_zero() && opened_at.elapsed() >= self.linger;
+        if self.pending.len() >= self.batch_size || lingered {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    /// Hand out whatever is pending, e.g. when the input is exhausted
+    pub fn flush(&mut self) -> Option<Vec<Record>> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        self.opened_at = None;
+        self.batches_flushed += 1;
+        Some(std::mem::replace(
+            &mut self.pending,
+            Vec::with_capacity(self.batch_size),
+        ))
+    }
+
+    /// Number of batches handed out so far
+    pub fn batches_flushed(&self) -> usize {
+        self.batches_flushed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use std::collections::BTreeMap;
+
+    fn record(n: u8) -> Record {
+        Record {
+            key: None,
+            value: Some(vec![n]),
+            headers: BTreeMap::new(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn flushes_when_batch_size_is_reached() {
+        let mut batcher = RecordBatcher::new(3, Duration::ZERO);
+        assert!(batcher.push(record(1)).is_none());
+        assert!(batcher.push(record(2)).is_none());
+        assert_eq!(batcher.push(record(3)).map(|batch| batch.len()), Some(3));
+        assert!(batcher.push(record(4)).is_none());
+        assert_eq!(batcher.batches_flushed(), 1);
+    }
+
+    #[test]
+    fn zero_linger_does_not_flush_early() {
+        let mut batcher = RecordBatcher::new(100, Duration::ZERO);
+        for n in 0..99 {
+            assert!(batcher.push(record(n)).is_none());
+        }
+        assert_eq!(batcher.batches_flushed(), 0);
+    }
+
+    #[test]
+    fn flushes_once_the_oldest_record_has_lingered() {
+        let mut batcher = RecordBatcher::new(100, Duration::from_millis(10));
+        assert!(batcher.push(record(1)).is_none());
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(batcher.push(record(2)).map(|batch| batch.len()), Some(2));
+        // The next batch's linger starts with its own first record
+        assert!(batcher.push(record(3)).is_none());
+    }
+
+    #[test]
+    fn final_flush_hands_out_the_remainder_once() {
+        let mut batcher = RecordBatcher::new(2, Duration::ZERO);
+        for n in 0..3 {
+            batcher.push(record(n));
+        }
+        assert_eq!(batcher.flush().map(|batch| batch.len()), Some(1));
+        assert!(batcher.flush().is_none());
+        assert_eq!(batcher.batches_flushed(), 2);
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Thresholds for `CircuitBreaker`, taken from `AppConfig`
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitSettings {
+    /// Consecutive connection failures within `window` that open the circuit; 0 disables it
+    pub threshold: u32,
+    pub window: Duration,
+    /// How long the circuit stays open before a single probe connection is let through
+    pub cooldown: Duration,
+}
+
+/// Stops repeated connection attempts to a broker that just failed several times in a row.
+///
+/// Closed: attempts go through and failures are counted. Open: attempts are refused until the
+/// cooldown passes. Half-open: one probe attempt goes through; success closes the circuit and
+/// failure opens it for another cooldown. A probe that never reports back (e.g. its operation
+/// timed out and was dropped) is given up on after one cooldown.
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    failures: VecDeque<Instant>,
+    opened_at: Option<Instant>,
+    probe_started_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    /// Whether a connection attempt may go ahead now
+    pub fn allow(&mut self, settings: &CircuitSettings) -> bool {
+        if settings.threshold == 0 {
+            return true;
+        }
+        let Some(opened_at) = self.opened_at else {
+            return true;
+        };
+        if opened_at.elapsed() < settings.cooldown {
+            return false;
+        }
+        if let Some(probe_started_at) = self.probe_started_at {
+            if probe_started_at.elapsed() < settings.cooldown {
+                return false;
+            }
+        }
+        self.probe_started_at = Some(Instant::now());
+        true
+    }
+
+    /// Record a successful connection; returns `true` if this closed an open circuit
+    pub fn record_success(&mut self) -> bool {
+        self.failures.clear();
+        self.probe_started_at = None;
+        self.opened_at.take().is_some()
+    }
+
+    /// Record a failed connection; returns `true` if this opened a closed circuit
+    pub fn record_failure(&mut self, settings: &CircuitSettings) -> bool {
+        if settings.threshold == 0 {
+            return false;
+        }
+        let now = Instant::now();
+        if self.opened_at.is_some() {
+            // The half-open probe failed; stay open for another cooldown
+            self.opened_at = Some(now);
+            self.probe_started_at = None;
+            return false;
+        }
+
+        self.failures.push_back(now);
+        while self
+            .failures
+            .front()
+            .is_some_and(|failed_at| now.duration_since(*failed_at) > settings.window)
+        {
+            self.failures.pop_front();
+        }
+        if self.failures.len() >= settings.threshold as usize {
+            self.failures.clear();
+            self.opened_at = Some(now);
+            return true;
+        }
+        false
+    }
+
+    /// Whether the circuit is currently open or half-open
+    pub fn is_open(&self) -> bool {
+        self.opened_at.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_millis(30);
+
+    fn settings(threshold: u32) -> CircuitSettings {
+        CircuitSettings {
+            threshold,
+            window: Duration::from_secs(60),
+            cooldown: COOLDOWN,
+        }
+    }
+
+    fn opened(settings: &CircuitSettings) -> CircuitBreaker {
+        let mut circuit = CircuitBreaker::default();
+        for _ in 1..settings.threshold {
+            assert!(!circuit.record_failure(settings));
+        }
+        assert!(circuit.record_failure(settings));
+        circuit
+    }
+
+    #[test]
+    fn opens_after_threshold_failures() {
+        let settings = settings(3);
+        let mut circuit = CircuitBreaker::default();
+        assert!(!circuit.record_failure(&settings));
+        assert!(!circuit.record_failure(&settings));
+        assert!(circuit.allow(&settings));
+        assert!(circuit.record_failure(&settings));
+        assert!(circuit.is_open());
+        assert!(!circuit.allow(&settings));
+    }
+
+    #[test]
+    fn failures_outside_the_window_are_forgotten() {
+        let settings = CircuitSettings {
+            window: Duration::from_millis(10),
+            ..settings(2)
+        };
+        let mut circuit = CircuitBreaker::default();
+        assert!(!circuit.record_failure(&settings));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!circuit.record_failure(&settings));
+        assert!(!circuit.is_open());
+    }
+
+    #[test]
+    fn success_resets_the_failure_count() {
+        let settings = settings(2);
+        let mut circuit = CircuitBreaker::default();
+        assert!(!circuit.record_failure(&settings));
+        assert!(!circuit.record_success());
+        assert!(!circuit.record_failure(&settings));
+        assert!(!circuit.is_open());
+    }
+
+    #[test]
+    fn zero_threshold_never_opens() {
+        let settings = settings(0);
+        let mut circuit = CircuitBreaker::default();
+        for _ in 0..10 {
+            assert!(!circuit.record_failure(&settings));
+        }
+        assert!(circuit.allow(&settings));
+        assert!(!circuit.is_open());
+    }
+
+    #[test]
+    fn half_open_lets_one_probe_through_after_the_cooldown() {
+        let settings = settings(1);
+        let mut circuit = opened(&settings);
+        assert!(!circuit.allow(&settings));
+
+        std::thread::sleep(COOLDOWN);
+        assert!(circuit.allow(&settings));
+        // Only the one probe while it is outstanding
+        assert!(!circuit.allow(&settings));
+
+        assert!(circuit.record_success());
+        assert!(!circuit.is_open());
+        assert!(circuit.allow(&settings));
+    }
+
+    #[test]
+    fn failed_probe_reopens_for_another_cooldown() {
+        let settings = settings(1);
+        let mut circuit = opened(&settings);
+        std::thread::sleep(COOLDOWN);
+        assert!(circuit.allow(&settings));
+
+        // Already open, so this isn't reported as a new opening
+        assert!(!circuit.record_failure(&settings));
+        assert!(circuit.is_open());
+        assert!(!circuit.allow(&settings));
+
+        std::thread::sleep(COOLDOWN);
+        assert!(circuit.allow(&settings));
+    }
+
+    #[test]
+    fn unanswered_probe_is_given_up_after_a_cooldown() {
+        let settings = settings(1);
+        let mut circuit = opened(&settings);
+        std::thread::sleep(COOLDOWN);
+        assert!(circuit.allow(&settings));
+        assert!(!circuit.allow(&settings));
+
+        std::thread::sleep(COOLDOWN);
+        assert!(circuit.allow(&settings));
+    }
+}
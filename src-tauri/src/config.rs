@@ -1,41 +1,80 @@
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::PathBuf;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use crate::kafka::DecodePolicy;
+use crate::secrets::modified_time;
 
 /// Security protocol for Kafka connection
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Hash)]
 pub enum SecurityProtocol {
+    #[default]
     Plaintext,
     Ssl,
     SaslPlaintext,
     SaslSsl,
 }
 
-impl Default for SecurityProtocol {
-    fn default() -> Self {
-        Self::Plaintext
-    }
-}
-
 /// SASL authentication mechanism
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Hash)]
 pub enum SaslMechanism {
+    #[default]
     Plain,
     ScramSha256,
     ScramSha512,
 }
 
-impl Default for SaslMechanism {
-    fn default() -> Self {
-        Self::Plain
-    }
+/// Compression codec applied to produced record batches (mirrors rskafka's `Compression`)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum CompressionCodec {
+    #[default]
+    #[serde(alias = "None")]
+    NoCompression,
+    Gzip,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+/// Acknowledgements the broker must collect before confirming a produce (Kafka's `acks`)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum Acks {
+    /// `acks=1`: the partition leader alone
+    Leader,
+    /// `acks=all`: every in-sync replica
+    #[default]
+    All,
+}
+
+/// Settings applied when operating on one topic; unset fields fall back to the global config
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TopicSettings {
+    /// Decode policy for consumes that don't pass one
+    pub decode_policy: Option<DecodePolicy>,
+    /// Partition for sends without a key, instead of partition 0
+    pub default_partition: Option<i32>,
+    /// Compression for sends that don't pass a codec, instead of `compression`
+    pub compression: Option<CompressionCodec>,
 }
 
 /// Application configuration for Kafka connection
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
+    /// Label for this connection in the UI, e.g. "PROD"; metadata only
+    #[serde(default)]
+    pub display_name: String,
+    /// Badge colour for `display_name`, as a CSS colour; metadata only
+    #[serde(default)]
+    pub color: Option<String>,
     pub broker: String,
     pub topic: String,
+    /// Client id sent to the brokers with every request (rskafka's default when empty)
     pub client_id: String,
     #[serde(default)]
     pub security_protocol: SecurityProtocol,
@@ -53,11 +92,141 @@ pub struct AppConfig {
     pub ssl_client_key_path: String,
     #[serde(default)]
     pub ssl_skip_verification: bool,
+    /// Inline PEM alternatives to the `*_path` fields; a non-empty inline value wins
+    #[serde(default)]
+    pub ssl_ca_cert_pem: String,
+    #[serde(default)]
+    pub ssl_client_cert_pem: String,
+    #[serde(default)]
+    pub ssl_client_key_pem: String,
+    /// Codec for produced batches unless a topic override or the send itself picks one
+    #[serde(default)]
+    pub compression: CompressionCodec,
+    /// SOCKS5 proxy (`socks5://host:port`) used to reach the brokers
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+    /// How long cluster metadata is cached before being re-fetched
+    #[serde(default = "default_metadata_cache_ttl_secs")]
+    pub metadata_cache_ttl_secs: u64,
+    /// Maximum number of produce requests outstanding at once in batch sends
+    #[serde(default = "default_max_in_flight")]
+    pub max_in_flight: usize,
+    /// Key used by `send_message` when the call doesn't pass one
+    #[serde(default)]
+    pub default_key: Option<String>,
+    /// Headers added to every record sent by `send_message`; per-call headers with the same
+    /// name take precedence
+    #[serde(default)]
+    pub default_headers: HashMap<String, String>,
+    /// Records per produce request in batch sends
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// How long a batch send waits for a batch to fill before flushing it anyway; 0 waits until
+    /// the batch is full
+    #[serde(default)]
+    pub linger_ms: u64,
+    /// How often the idle connection is pinged to keep it open; 0 disables keep-alive
+    #[serde(default = "default_keep_alive_interval_secs")]
+    pub keep_alive_interval_secs: u64,
+    /// Consecutive connection failures within `circuit_breaker_window_secs` after which
+    /// operations fail fast for `circuit_breaker_cooldown_secs`; 0 disables the breaker
+    #[serde(default = "default_circuit_breaker_threshold")]
+    pub circuit_breaker_threshold: u32,
+    #[serde(default = "default_circuit_breaker_window_secs")]
+    pub circuit_breaker_window_secs: u64,
+    #[serde(default = "default_circuit_breaker_cooldown_secs")]
+    pub circuit_breaker_cooldown_secs: u64,
+    /// Spool messages whose send fails because the broker is unreachable, for
+    /// `flush_offline_queue` to replay later
+    #[serde(default)]
+    pub enable_offline_queue: bool,
+    /// Keep topics and records in process memory instead of talking to a broker, for working on
+    /// the UI without Kafka. Covers connecting, sending, topic creation/listing, consuming (incl.
+    /// tailing, `get_message_at`, `consume_new_since_last` and `offsets_for_times`) and topic
+    /// stats; other operations fail with `Unsupported`.
+    #[serde(default)]
+    pub in_memory: bool,
+    /// Hand records to a background produce and return without waiting for the broker's
+    /// response. Delivery is NOT guaranteed: failures are only logged and sends report no offset.
+    /// Meant for load tests where waiting on every acknowledgement is the bottleneck.
+    #[serde(default)]
+    pub fire_and_forget: bool,
+    /// JSON or TOML file holding `{ username, password }` for SASL, used instead of
+    /// `sasl_username`/`sasl_password` so this file can be shared without secrets
+    #[serde(default)]
+    pub sasl_credentials_path: Option<String>,
+    /// Extra attempts at building the client when connecting fails for a reason other than
+    /// rejected credentials or invalid settings, e.g. during a cluster rebalance; 0 tries once
+    #[serde(default)]
+    pub connect_retries: u32,
+    /// Wait between connection attempts
+    #[serde(default = "default_connect_retry_backoff_ms")]
+    pub connect_retry_backoff_ms: u64,
+    /// Add a `client-id` header holding `client_id` to every record produced with user or test
+    /// data (not the probe records of `can_produce` and `latency_probe`), so consumers can trace
+    /// where a record came from. It replaces a user-supplied `client-id` header (including one in
+    /// `default_headers`), while other user headers are kept. Imported records are not stamped, so
+    /// they keep their exported headers. This is separate from the protocol-level client id, which
+    /// only the broker sees.
+    #[serde(default)]
+    pub stamp_client_id_header: bool,
+    /// Acknowledgements required for `send_message`. rskafka always requests `acks=all`, so
+    /// `Leader` is rejected when sending rather than silently upgraded
+    #[serde(default)]
+    pub acks: Acks,
+    /// How long `send_message` may take, from connecting to the acknowledgement
+    #[serde(default = "default_produce_timeout_ms")]
+    pub produce_timeout_ms: u64,
+    /// Check that sent values are valid JSON before producing them, unless the send says
+    /// otherwise
+    #[serde(default)]
+    pub validate_json: bool,
+    /// Per-topic settings, keyed by topic name
+    #[serde(default)]
+    pub topic_overrides: HashMap<String, TopicSettings>,
+}
+
+fn default_metadata_cache_ttl_secs() -> u64 {
+    5
+}
+
+fn default_max_in_flight() -> usize {
+    5
+}
+
+fn default_batch_size() -> usize {
+    1
+}
+
+fn default_keep_alive_interval_secs() -> u64 {
+    60
+}
+
+fn default_produce_timeout_ms() -> u64 {
+    10_000
+}
+
+fn default_connect_retry_backoff_ms() -> u64 {
+    1000
+}
+
+fn default_circuit_breaker_threshold() -> u32 {
+    3
+}
+
+fn default_circuit_breaker_window_secs() -> u64 {
+    60
+}
+
+fn default_circuit_breaker_cooldown_secs() -> u64 {
+    30
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
+            display_name: String::new(),
+            color: None,
             broker: "localhost:9092".to_string(),
             topic: "test-topic".to_string(),
             client_id: "kafka-msg-publisher".to_string(),
@@ -69,39 +238,242 @@ impl Default for AppConfig {
             ssl_client_cert_path: String::new(),
             ssl_client_key_path: String::new(),
             ssl_skip_verification: false,
+            ssl_ca_cert_pem: String::new(),
+            ssl_client_cert_pem: String::new(),
+            ssl_client_key_pem: String::new(),
+            compression: CompressionCodec::default(),
+            proxy_url: None,
+            metadata_cache_ttl_secs: default_metadata_cache_ttl_secs(),
+            max_in_flight: default_max_in_flight(),
+            default_key: None,
+            default_headers: HashMap::new(),
+            batch_size: default_batch_size(),
+            linger_ms: 0,
+            keep_alive_interval_secs: default_keep_alive_interval_secs(),
+            circuit_breaker_threshold: default_circuit_breaker_threshold(),
+            circuit_breaker_window_secs: default_circuit_breaker_window_secs(),
+            circuit_breaker_cooldown_secs: default_circuit_breaker_cooldown_secs(),
+            enable_offline_queue: false,
+            in_memory: false,
+            fire_and_forget: false,
+            stamp_client_id_header: false,
+            acks: Acks::default(),
+            produce_timeout_ms: default_produce_timeout_ms(),
+            validate_json: false,
+            topic_overrides: HashMap::new(),
+            sasl_credentials_path: None,
+            connect_retries: 0,
+            connect_retry_backoff_ms: default_connect_retry_backoff_ms(),
         }
     }
 }
 
+/// Config template for a security protocol, plus the fields the user must fill in
+#[derive(Debug, Clone, Serialize)]
+pub struct SampleConfig {
+    pub config: AppConfig,
+    pub required_fields: Vec<String>,
+}
+
+/// Record header written when `stamp_client_id_header` is set
+const CLIENT_ID_HEADER: &str = "client-id";
+
+/// Environment variable that relocates the app's persisted files, e.g. for portable installs
+const CONFIG_DIR_ENV: &str = "KAFKA_MSG_PUBLISHER_CONFIG_DIR";
+
+/// Directory set at runtime with `set_config_dir`; takes precedence over the environment
+static CONFIG_DIR_OVERRIDE: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Directory holding the app's persisted files (config, consume positions): the runtime
+/// override, else `KAFKA_MSG_PUBLISHER_CONFIG_DIR`, else the platform config directory
+pub fn app_config_dir() -> Option<PathBuf> {
+    if let Some(dir) = CONFIG_DIR_OVERRIDE.read().unwrap().clone() {
+        return Some(dir);
+    }
+    match std::env::var_os(CONFIG_DIR_ENV) {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => dirs::config_dir().map(|dir| dir.join("kafka-msg-publisher")),
+    }
+}
+
+/// Point subsequent config, position and offline queue loads/saves at `dir`, creating it if
+/// needed. Already loaded state is not touched; `KafkaService::set_config_dir` reloads it.
+pub fn set_config_dir(dir: PathBuf) -> Result<(), ConfigError> {
+    fs::create_dir_all(&dir).map_err(|e| ConfigError::IoError(e.to_string()))?;
+    *CONFIG_DIR_OVERRIDE.write().unwrap() = Some(dir);
+    Ok(())
+}
+
+/// Delete `path`; a file that is already gone is not an error
+pub fn remove_if_exists(path: &Path) -> Result<(), ConfigError> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(ConfigError::IoError(e.to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Replace `path` with `content` without ever leaving it half-written: the content goes to a
+/// temporary file in the same directory, which is then renamed over `path`. With `backup`, the
+/// previous file is first copied to `<name>.bak`.
+pub fn write_atomically(path: &Path, content: &str, backup: bool) -> Result<(), ConfigError> {
+    let io_error = |e: std::io::Error| ConfigError::IoError(e.to_string());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_error)?;
+    }
+
+    let tmp_path = with_suffix(path, ".tmp");
+    let mut tmp = fs::File::create(&tmp_path).map_err(io_error)?;
+    tmp.write_all(content.as_bytes()).map_err(io_error)?;
+    tmp.sync_all().map_err(io_error)?;
+    drop(tmp);
+
+    if backup && path.exists() {
+        fs::copy(path, with_suffix(path, ".bak")).map_err(io_error)?;
+    }
+    fs::rename(&tmp_path, path).map_err(io_error)
+}
+
+/// `path` with `suffix` appended to its file name, e.g. `config.json.bak`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
 impl AppConfig {
     /// Get the config file path in the app data directory
     fn config_path() -> Option<PathBuf> {
-        dirs::config_dir().map(|dir| dir.join("kafka-msg-publisher").join("config.json"))
+        app_config_dir().map(|dir| dir.join("config.json"))
     }
 
-    /// Load config from disk, or return default if not found
+    /// Load config from disk, falling back to the `.bak` of the previous save when the config is
+    /// missing or unreadable, and to the default when neither loads
     pub fn load() -> Self {
+        let read = |path: PathBuf| {
+            fs::read_to_string(path)
+                .ok()
+                .and_then(|content| serde_json::from_str(&content).ok())
+        };
         Self::config_path()
-            .and_then(|path| fs::read_to_string(path).ok())
-            .and_then(|content| serde_json::from_str(&content).ok())
+            .and_then(|path| read(path.clone()).or_else(|| read(with_suffix(&path, ".bak"))))
             .unwrap_or_default()
     }
 
+    /// Delete the saved config and its `.bak`, so the next load starts from the defaults
+    pub fn delete_saved() -> Result<(), ConfigError> {
+        let path = Self::config_path().ok_or(ConfigError::NoConfigDir)?;
+        remove_if_exists(&path)?;
+        remove_if_exists(&with_suffix(&path, ".bak"))
+    }
+
+    /// Template config for `protocol`: fields relevant to it hold placeholder values and the
+    /// rest are left blank
+    pub fn sample_config(protocol: SecurityProtocol) -> SampleConfig {
+        let uses_tls = matches!(protocol, SecurityProtocol::Ssl | SecurityProtocol::SaslSsl);
+        let uses_sasl = matches!(
+            protocol,
+            SecurityProtocol::SaslPlaintext | SecurityProtocol::SaslSsl
+        );
+
+        let mut config = AppConfig {
+            broker: if uses_tls {
+                "broker1.example.com:9093".to_string()
+            } else {
+                "localhost:9092".to_string()
+            },
+            security_protocol: protocol,
+            ..AppConfig::default()
+        };
+        let mut required_fields = vec!["broker", "topic"];
+
+        if uses_tls {
+            // Optional: without a CA the system roots are used, without a client cert no mTLS
+            config.ssl_ca_cert_path = "/path/to/ca.pem".to_string();
+            config.ssl_client_cert_path = "/path/to/client.pem".to_string();
+            config.ssl_client_key_path = "/path/to/client.key".to_string();
+        }
+        if uses_sasl {
+            config.sasl_username = "username".to_string();
+            config.sasl_password = "password".to_string();
+            required_fields.extend(["sasl_mechanism", "sasl_username", "sasl_password"]);
+        }
+
+        SampleConfig {
+            config,
+            required_fields: required_fields.into_iter().map(String::from).collect(),
+        }
+    }
+
+    /// Hash of the settings that affect how the client connects (brokers, security, SASL, TLS,
+    /// proxy). Topic and other per-operation settings are left out.
+    pub fn connection_fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.broker.hash(&mut hasher);
+        self.client_id.hash(&mut hasher);
+        self.security_protocol.hash(&mut hasher);
+        self.sasl_mechanism.hash(&mut hasher);
+        self.sasl_username.hash(&mut hasher);
+        self.sasl_password.hash(&mut hasher);
+        self.ssl_ca_cert_path.hash(&mut hasher);
+        self.ssl_client_cert_path.hash(&mut hasher);
+        self.ssl_client_key_path.hash(&mut hasher);
+        self.ssl_skip_verification.hash(&mut hasher);
+        self.ssl_ca_cert_pem.hash(&mut hasher);
+        self.ssl_client_cert_pem.hash(&mut hasher);
+        self.ssl_client_key_pem.hash(&mut hasher);
+        self.proxy_url.hash(&mut hasher);
+        // A rewritten credentials file needs a new connection just like edited inline ones
+        if let Some(path) = self.sasl_credentials_file() {
+            path.hash(&mut hasher);
+            modified_time(&path).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// `sasl_credentials_path`, when set
+    pub fn sasl_credentials_file(&self) -> Option<PathBuf> {
+        self.sasl_credentials_path
+            .as_deref()
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+    }
+
+    /// Overrides for `topic`, all unset when it has none
+    pub fn topic_settings(&self, topic: &str) -> TopicSettings {
+        self.topic_overrides.get(topic).cloned().unwrap_or_default()
+    }
+
+    /// Headers added to every produced record (see `stamp_client_id_header`)
+    pub fn stamped_headers(&self) -> BTreeMap<String, Vec<u8>> {
+        let mut headers = BTreeMap::new();
+        if self.stamp_client_id_header {
+            headers.insert(
+                CLIENT_ID_HEADER.to_string(),
+                self.client_id.clone().into_bytes(),
+            );
+        }
+        headers
+    }
+
+    /// Default compression for sends to `topic`
+    pub fn compression_for(&self, topic: &str) -> CompressionCodec {
+        self.topic_settings(topic)
+            .compression
+            .unwrap_or(self.compression)
+    }
+
     /// Save config to disk
     pub fn save(&self) -> Result<(), ConfigError> {
         let path = Self::config_path().ok_or(ConfigError::NoConfigDir)?;
-        
-        // Create parent directories if needed
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent).map_err(|e| ConfigError::IoError(e.to_string()))?;
-        }
 
         let content = serde_json::to_string_pretty(self)
             .map_err(|e| ConfigError::SerializeError(e.to_string()))?;
-        
-        fs::write(path, content).map_err(|e| ConfigError::IoError(e.to_string()))?;
-        
-        Ok(())
+
+        // A crash mid-save leaves either the old or the new config, never a truncated one
+        write_atomically(&path, &content, true)
     }
 }
 
@@ -110,10 +482,61 @@ impl AppConfig {
 pub enum ConfigError {
     #[error("Could not find config directory")]
     NoConfigDir,
-    
+
     #[error("IO error: {0}")]
     IoError(String),
-    
+
     #[error("Serialization error: {0}")]
     SerializeError(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changing_only_the_topic_keeps_the_connection() {
+        let config = AppConfig::default();
+        let retargeted = AppConfig {
+            topic: "another-topic".to_string(),
+            ..config.clone()
+        };
+        assert_eq!(
+            config.connection_fingerprint(),
+            retargeted.connection_fingerprint()
+        );
+    }
+
+    #[test]
+    fn changing_connection_settings_drops_the_connection() {
+        let config = AppConfig::default();
+        let changes = [
+            AppConfig {
+                broker: "other-broker:9092".to_string(),
+                ..config.clone()
+            },
+            AppConfig {
+                security_protocol: SecurityProtocol::SaslSsl,
+                ..config.clone()
+            },
+            AppConfig {
+                sasl_mechanism: SaslMechanism::ScramSha512,
+                ..config.clone()
+            },
+            AppConfig {
+                sasl_username: "alice".to_string(),
+                ..config.clone()
+            },
+            AppConfig {
+                sasl_password: "rotated".to_string(),
+                ..config.clone()
+            },
+        ];
+        for changed in changes {
+            assert_ne!(
+                config.connection_fingerprint(),
+                changed.connection_fingerprint()
+            );
+        }
+    }
+}
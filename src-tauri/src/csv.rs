@@ -0,0 +1,178 @@
+use serde::Deserialize;
+
+/// How `produce_from_csv` builds each record's value from a row
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub enum CsvValueFormat {
+    /// A JSON object of the row's fields keyed by column header
+    WholeRowJson,
+    /// The named column's field as-is
+    SingleColumn(String),
+}
+
+/// A CSV file's header row and data rows; every data row has one field per header column
+#[derive(Debug)]
+pub struct CsvTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl CsvTable {
+    /// Parse RFC 4180 CSV: comma-separated, fields optionally quoted with `"` (doubled inside
+    /// quotes), quoted fields may span lines, and rows end in `\n` or `\r\n`. The first row is
+    /// the header; blank lines are skipped. Errors name the file line the bad row starts on.
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut records = parse_records(content)?.into_iter();
+        let (_, headers) = records.next().ok_or("The CSV file is empty")?;
+        let rows = records
+            .map(|(line, row)| {
+                if row.len() == headers.len() {
+                    Ok(row)
+                } else {
+                    Err(format!(
+                        "Line {} has {} fields but the header has {}",
+                        line,
+                        row.len(),
+                        headers.len()
+                    ))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(CsvTable { headers, rows })
+    }
+
+    /// Index of the column named `name`
+    pub fn column(&self, name: &str) -> Result<usize, String> {
+        self.headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or_else(|| format!("Column '{}' is not in the CSV header", name))
+    }
+}
+
+/// Split `content` into records, each with the line it starts on
+fn parse_records(content: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    // The current field was quoted and its closing quote has been read
+    let mut quote_closed = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => {
+                    in_quotes = false;
+                    quote_closed = true;
+                }
+                c => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+            }
+            continue;
+        }
+
+        match c {
+            ',' => {
+                record.push(std::mem::take(&mut field));
+                quote_closed = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                quote_closed = false;
+                // A blank line parses as one empty field
+                if record.len() > 1 || !record[0].is_empty() {
+                    records.push((record_line, std::mem::take(&mut record)));
+                } else {
+                    record.clear();
+                }
+                line += 1;
+                record_line = line;
+            }
+            c if quote_closed => {
+                return Err(format!(
+                    "Unexpected '{}' after a closing quote at line {}",
+                    c, line
+                ));
+            }
+            '"' if field.is_empty() => in_quotes = true,
+            c => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(format!(
+            "Unterminated quoted field starting at line {}",
+            record_line
+        ));
+    }
+    if !field.is_empty() || !record.is_empty() || quote_closed {
+        record.push(field);
+        records.push((record_line, record));
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quoted_fields_with_commas_and_doubled_quotes() {
+        let table = CsvTable::parse("id,text\n1,\"a, \"\"quoted\"\" b\"\n").unwrap();
+        assert_eq!(table.headers, ["id", "text"]);
+        assert_eq!(table.rows, [["1", "a, \"quoted\" b"]]);
+    }
+
+    #[test]
+    fn quoted_fields_may_span_lines() {
+        let table = CsvTable::parse("id,text\n1,\"first\nsecond\"\n2,x").unwrap();
+        assert_eq!(table.rows, [["1", "first\nsecond"], ["2", "x"]]);
+    }
+
+    #[test]
+    fn accepts_crlf_line_endings_and_skips_blank_lines() {
+        let table = CsvTable::parse("a,b\r\n1,2\r\n\r\n3,4\r\n").unwrap();
+        assert_eq!(table.headers, ["a", "b"]);
+        assert_eq!(table.rows, [["1", "2"], ["3", "4"]]);
+    }
+
+    #[test]
+    fn keeps_a_trailing_empty_quoted_field() {
+        let table = CsvTable::parse("a,b\n1,\"\"").unwrap();
+        assert_eq!(table.rows, [["1", ""]]);
+    }
+
+    #[test]
+    fn ragged_rows_report_their_file_line() {
+        // The quoted newline puts the short row on line 4
+        let error = CsvTable::parse("a,b\n1,\"x\ny\"\n3\n").unwrap_err();
+        assert_eq!(error, "Line 4 has 1 fields but the header has 2");
+    }
+
+    #[test]
+    fn rejects_text_after_a_closing_quote() {
+        let error = CsvTable::parse("a\n\"ab\"c\n").unwrap_err();
+        assert_eq!(error, "Unexpected 'c' after a closing quote at line 2");
+    }
+
+    #[test]
+    fn rejects_unterminated_quotes_and_empty_files() {
+        assert_eq!(
+            CsvTable::parse("a\n\"open\n").unwrap_err(),
+            "Unterminated quoted field starting at line 2"
+        );
+        assert_eq!(CsvTable::parse("").unwrap_err(), "The CSV file is empty");
+    }
+}
@@ -0,0 +1,214 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{TimeZone, Utc};
+use rskafka::record::{Record, RecordAndOffset};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How bytes are written in an exported record field
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ByteEncoding {
+    #[default]
+    Utf8,
+    Base64,
+}
+
+/// Write bytes as UTF-8 text when they are valid UTF-8, otherwise as base64
+pub fn encode_bytes(bytes: &[u8]) -> (String, ByteEncoding) {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (text.to_string(), ByteEncoding::Utf8),
+        Err(_) => (BASE64.encode(bytes), ByteEncoding::Base64),
+    }
+}
+
+/// Reverse of `encode_bytes`
+pub fn decode_bytes(text: &str, encoding: ByteEncoding) -> Result<Vec<u8>, String> {
+    match encoding {
+        ByteEncoding::Utf8 => Ok(text.as_bytes().to_vec()),
+        ByteEncoding::Base64 => BASE64
+            .decode(text)
+            .map_err(|e| format!("Invalid base64: {}", e)),
+    }
+}
+
+/// A consumed record header value: UTF-8 text in `value`, or base64 in `value_base64` when the
+/// bytes aren't valid UTF-8, so binary headers (e.g. trace contexts) are never lossily decoded
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct HeaderValue {
+    pub value: Option<String>,
+    pub value_base64: Option<String>,
+}
+
+impl HeaderValue {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => HeaderValue {
+                value: Some(text.to_string()),
+                value_base64: None,
+            },
+            Err(_) => HeaderValue {
+                value: None,
+                value_base64: Some(BASE64.encode(bytes)),
+            },
+        }
+    }
+}
+
+/// Header values of a record, keyed by header name (always UTF-8 in Kafka)
+pub fn decode_headers(headers: &BTreeMap<String, Vec<u8>>) -> BTreeMap<String, HeaderValue> {
+    headers
+        .iter()
+        .map(|(key, value)| (key.clone(), HeaderValue::from_bytes(value)))
+        .collect()
+}
+
+/// A record header in the export format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedHeader {
+    pub key: String,
+    pub value: String,
+    #[serde(default)]
+    pub value_encoding: ByteEncoding,
+}
+
+/// One line of a JSONL export. Binary keys, values and header values are base64-encoded and
+/// marked as such, so importing the file reproduces the exact bytes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedRecord {
+    /// Source partition; informational, imports always produce to partition 0
+    #[serde(default)]
+    pub partition: i32,
+    #[serde(default)]
+    pub offset: i64,
+    pub timestamp: i64,
+    pub key: Option<String>,
+    #[serde(default)]
+    pub key_encoding: ByteEncoding,
+    pub value: Option<String>,
+    #[serde(default)]
+    pub value_encoding: ByteEncoding,
+    #[serde(default)]
+    pub headers: Vec<ExportedHeader>,
+}
+
+impl ExportedRecord {
+    /// Convert a record read from `partition` to the export format
+    pub fn from_record(record: &RecordAndOffset, partition: i32) -> Self {
+        Self::new(&record.record, partition, record.offset)
+    }
+
+    /// Convert a record stored (or to be stored) at `offset` on `partition`
+    pub fn new(record: &Record, partition: i32, offset: i64) -> Self {
+        let (key, key_encoding) = match &record.key {
+            Some(key) => {
+                let (text, encoding) = encode_bytes(key);
+                (Some(text), encoding)
+            }
+            None => (None, ByteEncoding::Utf8),
+        };
+        let (value, value_encoding) = match &record.value {
+            Some(value) => {
+                let (text, encoding) = encode_bytes(value);
+                (Some(text), encoding)
+            }
+            None => (None, ByteEncoding::Utf8),
+        };
+        let headers = record
+            .headers
+            .iter()
+            .map(|(key, value)| {
+                let (value, value_encoding) = encode_bytes(value);
+                ExportedHeader {
+                    key: key.clone(),
+                    value,
+                    value_encoding,
+                }
+            })
+            .collect();
+
+        ExportedRecord {
+            partition,
+            offset,
+            timestamp: record.timestamp.timestamp_millis(),
+            key,
+            key_encoding,
+            value,
+            value_encoding,
+            headers,
+        }
+    }
+
+    /// Rebuild the record for producing, keeping the original timestamp
+    pub fn into_record(self) -> Result<Record, String> {
+        let key = self
+            .key
+            .map(|key| decode_bytes(&key, self.key_encoding))
+            .transpose()
+            .map_err(|e| format!("key: {}", e))?;
+        let value = self
+            .value
+            .map(|value| decode_bytes(&value, self.value_encoding))
+            .transpose()
+            .map_err(|e| format!("value: {}", e))?;
+        let headers = self
+            .headers
+            .into_iter()
+            .map(|header| {
+                decode_bytes(&header.value, header.value_encoding)
+                    .map(|value| (header.key.clone(), value))
+                    .map_err(|e| format!("header '{}': {}", header.key, e))
+            })
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+        let timestamp = Utc
+            .timestamp_millis_opt(self.timestamp)
+            .single()
+            .ok_or_else(|| format!("Invalid timestamp {}", self.timestamp))?;
+
+        Ok(Record {
+            key,
+            value,
+            headers,
+            timestamp,
+        })
+    }
+}
+
+/// Formatting of an export file, for stable, diff-friendly output
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct ExportOptions {
+    /// Re-serialize JSON object and array values with their keys sorted
+    pub sort_keys: bool,
+    /// Indent JSON values and write each record as an indented document instead of one line
+    pub pretty: bool,
+}
+
+impl ExportOptions {
+    /// Serialize `record` as it should appear in the export file, without a trailing newline.
+    ///
+    /// With either option set, UTF-8 values holding a JSON object or array are re-serialized.
+    /// `serde_json` maps keep their keys sorted, so `pretty` sorts keys too, and re-serializing
+    /// drops insignificant whitespace: imported records then carry the normalized bytes.
+    pub fn render(&self, mut record: ExportedRecord) -> Result<String, serde_json::Error> {
+        if self.sort_keys || self.pretty {
+            if let (Some(value), ByteEncoding::Utf8) = (&record.value, record.value_encoding) {
+                if let Ok(json @ (serde_json::Value::Object(_) | serde_json::Value::Array(_))) =
+                    serde_json::from_str::<serde_json::Value>(value)
+                {
+                    record.value = Some(if self.pretty {
+                        serde_json::to_string_pretty(&json)?
+                    } else {
+                        serde_json::to_string(&json)?
+                    });
+                }
+            }
+        }
+
+        if self.pretty {
+            serde_json::to_string_pretty(&record)
+        } else {
+            serde_json::to_string(&record)
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use serde::Serialize;
+
+use crate::export::encode_bytes;
+
+/// Values shown in a format report
+const SAMPLE_VALUES: usize = 5;
+/// Characters kept of each sample value
+const SAMPLE_VALUE_CHARS: usize = 200;
+
+/// Record value format guessed from a sample
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub enum RecordFormat {
+    Json,
+    /// Confluent wire format (magic byte 0 and a schema id) followed by an Avro body
+    Avro,
+    /// Confluent wire format followed by Protobuf message indexes and body
+    Protobuf,
+    Text,
+    Binary,
+    /// No values were sampled
+    Unknown,
+}
+
+/// Heuristic guess at what a topic's values contain, to pick a decode mode before consuming
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicFormatReport {
+    pub likely_format: RecordFormat,
+    /// Fraction of sampled values matching `likely_format`
+    pub confidence: f64,
+    /// Fraction of sampled values that parse as a JSON object or array
+    pub json_ratio: f64,
+    /// Fraction of sampled values starting with the Confluent magic byte and a schema id
+    pub magic_byte_ratio: f64,
+    /// Fraction of sampled values that are valid UTF-8
+    pub utf8_ratio: f64,
+    /// The first few values, as text or base64 and cut to a short preview
+    pub sample_values: Vec<String>,
+}
+
+impl TopicFormatReport {
+    /// Classify each value and pick the most common format. Null values (tombstones) are
+    /// skipped.
+    ///
+    /// Confluent-framed values are Protobuf when the byte after the schema id is 0 (the common
+    /// single-message index), otherwise Avro; this can misread an Avro body starting with 0.
+    pub fn from_values<'a>(values: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let (mut json, mut avro, mut protobuf, mut text, mut binary, mut utf8) = (0, 0, 0, 0, 0, 0);
+        let mut sample_values = Vec::new();
+        for value in values {
+            if sample_values.len() < SAMPLE_VALUES {
+                sample_values.push(
+                    encode_bytes(value)
+                        .0
+                        .chars()
+                        .take(SAMPLE_VALUE_CHARS)
+                        .collect(),
+                );
+            }
+            let is_utf8 = std::str::from_utf8(value).is_ok();
+            if is_utf8 {
+                utf8 += 1;
+            }
+
+            if value.len() > 5 && value[0] == 0 {
+                if value[5] == 0 {
+                    protobuf += 1;
+                } else {
+                    avro += 1;
+                }
+            } else if is_json_document(value) {
+                json += 1;
+            } else if is_utf8 {
+                text += 1;
+            } else {
+                binary += 1;
+            }
+        }
+
+        let count = json + avro + protobuf + text + binary;
+        if count == 0 {
+            return Self {
+                likely_format: RecordFormat::Unknown,
+                confidence: 0.0,
+                json_ratio: 0.0,
+                magic_byte_ratio: 0.0,
+                utf8_ratio: 0.0,
+                sample_values,
+            };
+        }
+
+        let ratio = |n: usize| n as f64 / count as f64;
+        let (likely_format, matched) = [
+            (RecordFormat::Json, json),
+            (RecordFormat::Avro, avro),
+            (RecordFormat::Protobuf, protobuf),
+            (RecordFormat::Text, text),
+            (RecordFormat::Binary, binary),
+        ]
+        .into_iter()
+        // Reversed so ties go to the earlier, more specific format
+        .rev()
+        .max_by_key(|(_, n)| *n)
+        .unwrap_or((RecordFormat::Unknown, 0));
+
+        Self {
+            likely_format,
+            confidence: ratio(matched),
+            json_ratio: ratio(json),
+            magic_byte_ratio: ratio(avro + protobuf),
+            utf8_ratio: ratio(utf8),
+            sample_values,
+        }
+    }
+}
+
+/// Whether `value` is a JSON object or array; bare scalars like `42` are treated as text
+fn is_json_document(value: &[u8]) -> bool {
+    matches!(
+        serde_json::from_slice::<serde_json::Value>(value),
+        Ok(serde_json::Value::Object(_) | serde_json::Value::Array(_))
+    )
+}
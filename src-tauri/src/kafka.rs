@@ -1,22 +1,666 @@
-use rskafka::client::partition::{Compression, OffsetAt, UnknownTopicHandling};
-use rskafka::client::{ClientBuilder, Credentials, SaslConfig};
-use rskafka::record::Record;
-use chrono::Utc;
-use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
-use serde::Serialize;
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use rskafka::client::error::ProtocolError;
+use rskafka::client::partition::{Compression, OffsetAt, PartitionClient, UnknownTopicHandling};
+use rskafka::client::{Client, ClientBuilder, Credentials, SaslConfig};
+use rskafka::record::{Record, RecordAndOffset};
+use rskafka::BackoffConfig;
+#[cfg(feature = "dangerous-tls")]
+use rustls::pki_types::ServerName;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
 use std::io::BufReader;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::SystemTime;
-use tokio::sync::Mutex;
+use std::time::{Instant, SystemTime};
+use tokio::sync::{broadcast, Mutex, Semaphore};
+use tokio::task::JoinSet;
+use uuid::Uuid;
 
-use crate::config::{AppConfig, SaslMechanism, SecurityProtocol};
+use crate::backend::KafkaBackend;
+use crate::batcher::RecordBatcher;
+use crate::circuit::{CircuitBreaker, CircuitSettings};
+use crate::config::{
+    Acks, AppConfig, CompressionCodec, ConfigError, SaslMechanism, SecurityProtocol, TopicSettings,
+};
+use crate::csv::{CsvTable, CsvValueFormat};
+use crate::export::{
+    decode_bytes, decode_headers, encode_bytes, ByteEncoding, ExportOptions, ExportedRecord,
+    HeaderValue,
+};
+use crate::format::TopicFormatReport;
+use crate::memory::InMemoryBackend;
+use crate::operations::OperationRegistry;
+use crate::partitioner::partition_for_key;
+use crate::positions::PositionStore;
+use crate::secrets::load_sasl_credentials;
+use crate::spool::OfflineQueue;
+use crate::template::MessageTemplate;
 
 /// Result of a message send operation
 #[derive(Debug, Clone, Serialize)]
 pub struct SendResult {
     pub success: bool,
     pub message: String,
+    /// Seconds since the epoch; kept for compatibility, prefer `timestamp_ms`
     pub timestamp: u64,
+    pub timestamp_ms: i64,
+    /// `timestamp_ms` as RFC 3339 in UTC
+    pub timestamp_iso: String,
+    /// Compression codec actually used for the produce request
+    pub compression: String,
+    /// Whether broker-level (producer id/epoch) idempotence was active. rskafka does not
+    /// implement the idempotent producer protocol, so this is currently always `false`.
+    pub broker_idempotence: bool,
+    /// Idempotency key attached to the record when sending in idempotent mode
+    pub idempotency_key: Option<String>,
+    /// The broker was unreachable and the message was spooled to the offline queue instead
+    pub queued_offline: bool,
+    /// Whether the topic uses LogAppendTime, so a caller-supplied `timestamp_ms` was replaced by
+    /// the broker. Detecting this needs DescribeConfigs (`message.timestamp.type`) or the produce
+    /// response's `log_append_time_ms`, neither of which rskafka exposes, so this is currently
+    /// always `None`.
+    pub timestamp_overridden_by_broker: Option<bool>,
+    /// Broker quota throttle reported in the produce response. rskafka handles throttling
+    /// internally (it waits out `throttle_time_ms` and retries) without exposing the value, so
+    /// this is currently always `None`; a throttled send shows up only as a slower send.
+    pub throttle_time_ms: Option<i32>,
+    /// Partition the record was written to; `None` when nothing was produced (duplicate skips,
+    /// queued sends)
+    pub partition: Option<i32>,
+    /// Offset the broker assigned to the record; `None` when no acknowledgement was awaited
+    /// (`fire_and_forget`, duplicate skips, queued sends)
+    pub offset: Option<i64>,
+    /// Outcome of all copies when sent with `repeat`; the rest of the result describes the last
+    /// copy sent
+    pub repeat: Option<RepeatSummary>,
+}
+
+/// Outcome of a send with `SendOptions::repeat`
+#[derive(Debug, Clone, Serialize)]
+pub struct RepeatSummary {
+    pub requested: u32,
+    pub succeeded: u32,
+    pub failed: u32,
+    /// The loop stopped before sending every copy because the broker became unreachable
+    pub stopped_early: bool,
+    pub first_error: Option<String>,
+}
+
+impl SendResult {
+    /// A successful send, stamped with the current time
+    pub fn sent(message: &str, compression: Compression, idempotency_key: Option<String>) -> Self {
+        let (timestamp, timestamp_ms, timestamp_iso) = send_timestamps();
+        SendResult {
+            success: true,
+            message: message.to_string(),
+            timestamp,
+            timestamp_ms,
+            timestamp_iso,
+            compression: format!("{:?}", compression),
+            broker_idempotence: false,
+            idempotency_key,
+            queued_offline: false,
+            timestamp_overridden_by_broker: None,
+            throttle_time_ms: None,
+            partition: None,
+            offset: None,
+            repeat: None,
+        }
+    }
+
+    /// Record where the message was written
+    pub fn at(self, partition: i32, offset: Option<i64>) -> Self {
+        SendResult {
+            partition: Some(partition),
+            offset,
+            ..self
+        }
+    }
+}
+
+impl ConsumedMessage {
+    /// Convert a record read from `partition`, decoding key and value lossily
+    fn from_record(record: RecordAndOffset, partition: i32) -> Self {
+        ConsumedMessage {
+            partition,
+            offset: record.offset,
+            key: record
+                .record
+                .key
+                .map(|k| String::from_utf8_lossy(&k).to_string()),
+            key_encoding: ByteEncoding::Utf8,
+            value_full_length: record.record.value.as_ref().map_or(0, Vec::len),
+            value: record
+                .record
+                .value
+                .map(|v| String::from_utf8_lossy(&v).to_string()),
+            value_encoding: ByteEncoding::Utf8,
+            value_truncated: false,
+            headers: decode_headers(&record.record.headers),
+            timestamp: record.record.timestamp.timestamp_millis(),
+        }
+    }
+
+    /// Decode key and value according to `policy`; only `Strict` can fail
+    fn decode(
+        record: RecordAndOffset,
+        partition: i32,
+        policy: DecodePolicy,
+    ) -> Result<Self, String> {
+        let value_full_length = record.record.value.as_ref().map_or(0, Vec::len);
+        let (key, key_encoding) = policy.decode(record.record.key, "Key")?;
+        let (value, value_encoding) = policy.decode(record.record.value, "Value")?;
+
+        Ok(ConsumedMessage {
+            partition,
+            offset: record.offset,
+            key,
+            key_encoding,
+            value,
+            value_encoding,
+            value_truncated: false,
+            value_full_length,
+            headers: decode_headers(&record.record.headers),
+            timestamp: record.record.timestamp.timestamp_millis(),
+        })
+    }
+
+    /// Cut the returned value down to at most `max_bytes`, on a character boundary (and a whole
+    /// base64 quantum for base64 values), flagging it as truncated
+    fn truncate_value(&mut self, max_bytes: usize) {
+        let Some(value) = self.value.as_mut() else {
+            return;
+        };
+        if value.len() <= max_bytes {
+            return;
+        }
+        let mut end = match self.value_encoding {
+            ByteEncoding::Utf8 => max_bytes,
+            ByteEncoding::Base64 => max_bytes - max_bytes % 4,
+        };
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        value.truncate(end);
+        self.value_truncated = true;
+    }
+}
+
+/// How consumed keys and values that aren't valid UTF-8 are returned
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum DecodePolicy {
+    /// Substitute replacement characters for invalid sequences
+    #[default]
+    Lossy,
+    /// Leave the record out and report it as a decode error
+    Strict,
+    /// Return the bytes base64-encoded, flagged by `key_encoding`/`value_encoding`
+    Base64Fallback,
+}
+
+impl DecodePolicy {
+    /// Decode one record field (`what` names it in errors)
+    fn decode(
+        self,
+        bytes: Option<Vec<u8>>,
+        what: &str,
+    ) -> Result<(Option<String>, ByteEncoding), String> {
+        let Some(bytes) = bytes else {
+            return Ok((None, ByteEncoding::Utf8));
+        };
+        match String::from_utf8(bytes) {
+            Ok(text) => Ok((Some(text), ByteEncoding::Utf8)),
+            Err(e) => match self {
+                DecodePolicy::Lossy => Ok((
+                    Some(String::from_utf8_lossy(e.as_bytes()).to_string()),
+                    ByteEncoding::Utf8,
+                )),
+                DecodePolicy::Strict => {
+                    Err(format!("{} is not valid UTF-8: {}", what, e.utf8_error()))
+                }
+                DecodePolicy::Base64Fallback => {
+                    let (text, encoding) = encode_bytes(e.as_bytes());
+                    Ok((Some(text), encoding))
+                }
+            },
+        }
+    }
+}
+
+/// Per-call settings for `send_message` beyond the message and key
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SendOptions {
+    pub key_encoding: Encoding,
+    pub value_encoding: Encoding,
+    pub compression: Option<CompressionCodec>,
+    pub idempotent: bool,
+    pub idempotency_key: Option<String>,
+    /// Record timestamp in milliseconds since the epoch (now when unset)
+    pub timestamp_ms: Option<i64>,
+    /// Frame the value in the Confluent wire format with this schema id, for consumers using a
+    /// schema registry. The payload is sent as-is after the framing and isn't checked against
+    /// the schema.
+    pub confluent_schema_id: Option<u32>,
+    /// Partition to write to, instead of hashing the key (or the topic's default partition)
+    pub partition: Option<i32>,
+    /// Send the message this many times (once when unset), reporting a `RepeatSummary`
+    pub repeat: Option<u32>,
+    /// With `repeat`, append `-<n>` (counting from 0) to each copy of the message; `Utf8` values
+    /// only
+    pub append_sequence: bool,
+    /// Reject a value that isn't valid JSON before contacting the broker; falls back to the
+    /// configured `validate_json`
+    pub validate_json: Option<bool>,
+    /// Record headers; they win over `default_headers` of the same name
+    pub headers: HashMap<String, String>,
+}
+
+/// Everything `send_record` needs to produce one record, as a single object. Every field but
+/// `value` is optional, and new send settings become new `SendOptions` fields.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordSpec {
+    pub value: String,
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(flatten)]
+    pub options: SendOptions,
+}
+
+/// How a key or message string passed to a send command is turned into record bytes
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum Encoding {
+    /// Send the string's UTF-8 bytes
+    #[default]
+    Utf8,
+    /// Decode the string from base64 and send the raw bytes, e.g. to replay a captured binary
+    /// message exactly
+    Base64,
+    /// Send the string's UTF-8 bytes after checking it is valid JSON
+    Json,
+    /// Decode the string from hex digits (either case, optional whitespace between bytes)
+    Hex,
+}
+
+impl Encoding {
+    /// Convert `text` to bytes (`what` names the field in errors)
+    pub fn encode(self, text: String, what: &str) -> Result<Vec<u8>, KafkaError> {
+        match self {
+            Encoding::Utf8 => Ok(text.into_bytes()),
+            Encoding::Base64 => decode_bytes(&text, ByteEncoding::Base64)
+                .map_err(|e| KafkaError::InvalidInput(format!("{}: {}", what, e))),
+            Encoding::Json => match serde_json::from_str::<serde_json::Value>(&text) {
+                Ok(_) => Ok(text.into_bytes()),
+                Err(e) => Err(KafkaError::InvalidInput(format!(
+                    "{} is not valid JSON: {}",
+                    what, e
+                ))),
+            },
+            Encoding::Hex => {
+                let invalid = |e: String| KafkaError::InvalidInput(format!("{}: {}", what, e));
+                let digits: Vec<u8> = text.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+                if !digits.len().is_multiple_of(2) {
+                    return Err(invalid("Invalid hex: odd number of digits".to_string()));
+                }
+                digits
+                    .chunks(2)
+                    .map(|pair| {
+                        std::str::from_utf8(pair)
+                            .ok()
+                            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                            .ok_or_else(|| {
+                                invalid(format!(
+                                    "Invalid hex digits '{}'",
+                                    String::from_utf8_lossy(pair)
+                                ))
+                            })
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Consume result that separates cleanly decoded messages from records that failed to decode
+#[derive(Debug, Clone, Serialize)]
+pub struct DetailedConsumeResult {
+    pub messages: Vec<ConsumedMessage>,
+    pub decode_errors: Vec<DecodeError>,
+    /// The deadline was hit before `max_messages` were collected or the end was reached
+    pub truncated: bool,
+    /// Summed value sizes of the returned messages, in bytes
+    pub total_bytes: usize,
+}
+
+/// Extra consume settings beyond offset, count, dedup and decode policy
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ConsumeOptions {
+    /// Partition for single-partition consumes (0 when unset); `consume_all_partitions` reads
+    /// every partition regardless
+    pub partition: Option<i32>,
+    /// Total time budget for the consume in milliseconds (15 s when unset)
+    pub deadline_ms: Option<u64>,
+    /// Stop before the summed value sizes of the collected records would exceed this
+    pub max_total_bytes: Option<usize>,
+    /// What to do when the requested offset is outside the partition's range
+    pub offset_reset: OffsetReset,
+    /// Truncate each returned value to this many bytes
+    pub value_truncate_bytes: Option<usize>,
+    /// With `dedup_by_key`, return a key whose latest record is a tombstone as that tombstone
+    /// instead of dropping the key
+    pub include_tombstones: bool,
+    /// Order of the merged messages in `consume_all_partitions`
+    pub order_by: OrderBy,
+    /// Whether records of open or aborted transactions are returned
+    pub isolation_level: IsolationLevel,
+    /// Start at the first record with a timestamp at or after this time (milliseconds since the
+    /// epoch) instead of at the given offset; nothing is returned when there is no such record
+    pub from_timestamp_ms: Option<i64>,
+    /// Only return records whose key (decoded as UTF-8) equals this; records without a key never
+    /// match. Non-matching records are skipped without counting towards `max_messages`.
+    pub key_filter: Option<String>,
+    /// Match `key_filter` anywhere in the key instead of the whole key
+    pub key_filter_substring: bool,
+}
+
+impl ConsumeOptions {
+    /// Whether a record with `key` passes `key_filter`
+    pub fn key_matches(&self, key: Option<&[u8]>) -> bool {
+        match (self.key_filter.as_deref(), key) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(filter), Some(key)) if self.key_filter_substring => {
+                String::from_utf8_lossy(key).contains(filter)
+            }
+            (Some(filter), Some(key)) => String::from_utf8_lossy(key) == filter,
+        }
+    }
+}
+
+/// Which transactional records a fetch returns
+///
+/// rskafka always fetches with `read_committed`, so that is the default and `ReadUncommitted` is
+/// rejected against a real broker rather than silently ignored.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum IsolationLevel {
+    /// Also return records of transactions that are still open or were aborted
+    ReadUncommitted,
+    /// Return only records of committed transactions (and non-transactional records)
+    #[default]
+    ReadCommitted,
+}
+
+/// How `consume_all_partitions` orders the merged messages
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum OrderBy {
+    /// One global merge by timestamp (then partition and offset)
+    #[default]
+    Timestamp,
+    /// Partition by partition, each in offset order
+    PartitionThenOffset,
+}
+
+/// Where to start when the requested offset is below the earliest or beyond the latest offset,
+/// mirroring Kafka's `auto.offset.reset`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum OffsetReset {
+    /// Start from the earliest available offset
+    Earliest,
+    /// Start from the latest offset, so only newer messages would be returned (i.e. none yet)
+    Latest,
+    /// Fail with "offset out of range"
+    Error,
+    /// Move offsets below the range up to the earliest; offsets beyond it return nothing
+    #[default]
+    Clamp,
+}
+
+impl OffsetReset {
+    /// Resolve the offset to start reading at within `earliest..=latest`
+    pub fn resolve(self, offset: i64, earliest: i64, latest: i64) -> Result<i64, KafkaError> {
+        if (earliest..=latest).contains(&offset) {
+            return Ok(offset);
+        }
+        match self {
+            OffsetReset::Earliest => Ok(earliest),
+            OffsetReset::Latest => Ok(latest),
+            OffsetReset::Error => Err(KafkaError::ConsumeFailed(format!(
+                "offset out of range: {} is outside {}..={}",
+                offset, earliest, latest
+            ))),
+            OffsetReset::Clamp => Ok(offset.max(earliest)),
+        }
+    }
+}
+
+/// Raw records gathered by a consume
+pub struct FetchedRecords {
+    pub records: Vec<RecordAndOffset>,
+    pub truncated: bool,
+    /// Offset to resume from: one past the last record read, or the resolved start offset
+    pub next_offset: i64,
+}
+
+/// Merged result of consuming every partition of a topic
+#[derive(Debug, Clone, Serialize)]
+pub struct MultiPartitionConsumeResult {
+    /// Messages from all partitions, in `ConsumeOptions::order_by` order (timestamp by default)
+    pub messages: Vec<ConsumedMessage>,
+    /// Offset to resume from on each partition, suitable as the next call's `offsets`
+    pub next_offsets: HashMap<i32, i64>,
+    /// Some partition hit the deadline before collecting its `max_messages`
+    pub truncated: bool,
+}
+
+/// A record that could not be decoded
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodeError {
+    pub offset: i64,
+    pub error: String,
+}
+
+/// Offset a timestamp maps to on one partition
+#[derive(Debug, Clone, Serialize)]
+pub struct PartitionOffset {
+    pub partition: i32,
+    pub offset: i64,
+    /// No record exists at or after the timestamp, so `offset` is the partition's latest offset
+    pub latest_fallback: bool,
+}
+
+/// Offsets written by `produce_ordered`
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderedProduceResult {
+    pub partition: i32,
+    pub count: usize,
+    pub first_offset: Option<i64>,
+    pub last_offset: Option<i64>,
+}
+
+/// A place where `seq` headers stopped increasing by one
+#[derive(Debug, Clone, Serialize)]
+pub struct SequenceIssue {
+    pub offset: i64,
+    pub previous_seq: u64,
+    pub seq: u64,
+}
+
+/// Result of checking `seq` headers written by `produce_ordered`
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderVerification {
+    /// Records that carried a `seq` header
+    pub checked: usize,
+    /// Records without a parseable `seq` header (skipped)
+    pub missing_seq: usize,
+    /// `seq` jumped forward by more than one
+    pub gaps: Vec<SequenceIssue>,
+    /// `seq` did not increase
+    pub reorderings: Vec<SequenceIssue>,
+}
+
+/// Outcome of a `reliability_test` run
+#[derive(Debug, Clone, Serialize)]
+pub struct ReliabilityReport {
+    pub partition: i32,
+    pub produced: usize,
+    /// Records of this run read back, including duplicates
+    pub consumed: usize,
+    /// Sequence numbers that were produced but not read back
+    pub missing: Vec<u64>,
+    /// Sequence numbers read back more than once
+    pub duplicates: Vec<u64>,
+}
+
+/// An operation the app can't offer because the Kafka client library doesn't implement or
+/// expose what it needs
+#[derive(Debug, Clone, Serialize)]
+pub struct UnsupportedOperation {
+    pub operation: String,
+    /// Protocol APIs, or client internals, the operation needs
+    pub requires: String,
+}
+
+impl UnsupportedOperation {
+    /// Everything reported as unsupported, so the UI can hide it rather than call commands that
+    /// always fail
+    pub fn all() -> Vec<Self> {
+        [
+            (
+                "Consumer groups (list, describe, delete) and their lag",
+                "ListGroups, DescribeGroups, OffsetFetch, DeleteGroups",
+            ),
+            // rskafka decodes fetched batches internally and only hands out the records
+            (
+                "Record batch headers (producer id, attributes)",
+                "raw Fetch responses",
+            ),
+        ]
+        .into_iter()
+        .map(|(operation, requires)| Self {
+            operation: operation.to_string(),
+            requires: requires.to_string(),
+        })
+        .collect()
+    }
+}
+
+/// Outcome of producing a fanned-out message to one topic
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicSendResult {
+    pub topic: String,
+    pub success: bool,
+    pub offset: Option<i64>,
+    pub error: Option<String>,
+}
+
+/// Progress of a chunked produce session
+#[derive(Debug, Clone, Serialize)]
+pub struct ProduceSessionSummary {
+    pub session_id: String,
+    pub topic: String,
+    pub produced: usize,
+    pub chunks: usize,
+    pub first_offset: Option<i64>,
+    pub last_offset: Option<i64>,
+}
+
+/// An open chunked produce session
+struct ProduceSession {
+    partition_client: Arc<PartitionClient>,
+    compression: Compression,
+    /// `AppConfig::stamped_headers` when the session was opened
+    headers: BTreeMap<String, Vec<u8>>,
+    summary: ProduceSessionSummary,
+    /// When the session was opened or last produced a chunk
+    last_used: Instant,
+}
+
+/// Outcome of `rotate_credentials`
+#[derive(Debug, Clone, Serialize)]
+pub struct CredentialRotationResult {
+    /// Whether a connection with the new credentials succeeded
+    pub connected: bool,
+    /// Whether the previous credentials were restored after a failed test
+    pub rolled_back: bool,
+    pub error: Option<String>,
+}
+
+/// Result of exporting messages to a file
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportResult {
+    pub path: String,
+    pub count: usize,
+}
+
+/// Snapshot of the cluster written by `export_cluster_topology`
+#[derive(Debug, Clone, Serialize)]
+pub struct ClusterTopology {
+    /// When the snapshot was taken, RFC 3339
+    pub exported_at: String,
+    /// Bootstrap brokers the metadata was requested from
+    pub bootstrap_brokers: Vec<String>,
+    pub unreachable_brokers: Vec<String>,
+    pub metadata: ClusterMetadata,
+}
+
+/// Result of importing messages from a file
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportResult {
+    pub count: usize,
+    pub first_offset: Option<i64>,
+    pub last_offset: Option<i64>,
+}
+
+/// Progress of `produce_from_csv`, reported after each produce request
+#[derive(Debug, Clone, Serialize)]
+pub struct CsvProgress {
+    pub produced: usize,
+    pub total: usize,
+}
+
+/// Result of producing the rows of a CSV file
+#[derive(Debug, Clone, Serialize)]
+pub struct CsvProduceResult {
+    pub produced: usize,
+    /// Partitions written to, ascending
+    pub partitions: Vec<i32>,
+}
+
+/// Result of replaying the offline queue
+#[derive(Debug, Clone, Serialize)]
+pub struct OfflineFlushResult {
+    pub flushed: usize,
+    /// Messages dropped unsent because their idempotency key was already acknowledged
+    pub skipped: usize,
+    pub remaining: usize,
+    /// Why replay stopped early; the failed message stays at the head of the queue unless its
+    /// produce timed out after being sent
+    pub error: Option<String>,
+}
+
+/// Result of a batch send operation
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchSendResult {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Concurrency limit actually used for the batch
+    pub max_in_flight: usize,
+    /// Number of produce requests the records were grouped into
+    pub batches: usize,
+    pub first_error: Option<String>,
+    /// Records handed to background produces in `fire_and_forget` mode, whose outcome is unknown;
+    /// they count as neither succeeded nor failed
+    pub unconfirmed: usize,
+    /// Partition all records were sent to
+    pub partition: i32,
+    /// Lowest and highest offsets assigned to records of the batch; `None` when none was
+    /// acknowledged. Other producers' records may sit in between.
+    pub first_offset: Option<i64>,
+    pub last_offset: Option<i64>,
 }
 
 /// Result of a topic creation operation
@@ -30,12 +674,241 @@ pub struct TopicCreateResult {
 /// A consumed message from Kafka
 #[derive(Debug, Clone, Serialize)]
 pub struct ConsumedMessage {
+    /// Partition the message was read from
+    pub partition: i32,
     pub offset: i64,
     pub key: Option<String>,
+    pub key_encoding: ByteEncoding,
     pub value: Option<String>,
+    pub value_encoding: ByteEncoding,
+    /// `value` was cut short by `value_truncate_bytes`; fetch it whole with `get_message_at`
+    pub value_truncated: bool,
+    /// Size of the record's value in bytes, before any truncation
+    pub value_full_length: usize,
+    /// The record's headers by name, empty when it has none. Values that aren't UTF-8 come back
+    /// base64-encoded rather than lossily decoded.
+    pub headers: BTreeMap<String, HeaderValue>,
     pub timestamp: i64,
 }
 
+/// Size and shape statistics over a sample of a topic's messages
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicStats {
+    pub count: usize,
+    pub min_bytes: usize,
+    pub max_bytes: usize,
+    pub avg_bytes: f64,
+    pub p50: usize,
+    pub p95: usize,
+    /// Fraction of sampled records that carry a key
+    pub key_present_ratio: f64,
+    /// Fraction of sampled records whose value parses as JSON
+    pub json_ratio: f64,
+}
+
+impl TopicStats {
+    fn from_records(records: &[RecordAndOffset]) -> Self {
+        let count = records.len();
+        if count == 0 {
+            return Self {
+                count: 0,
+                min_bytes: 0,
+                max_bytes: 0,
+                avg_bytes: 0.0,
+                p50: 0,
+                p95: 0,
+                key_present_ratio: 0.0,
+                json_ratio: 0.0,
+            };
+        }
+
+        let mut sizes: Vec<usize> = records
+            .iter()
+            .map(|r| r.record.value.as_ref().map_or(0, |v| v.len()))
+            .collect();
+        sizes.sort_unstable();
+
+        let with_key = records.iter().filter(|r| r.record.key.is_some()).count();
+        let json = records
+            .iter()
+            .filter(|r| {
+                r.record
+                    .value
+                    .as_ref()
+                    .is_some_and(|v| serde_json::from_slice::<serde_json::Value>(v).is_ok())
+            })
+            .count();
+
+        Self {
+            count,
+            min_bytes: sizes[0],
+            max_bytes: sizes[count - 1],
+            avg_bytes: sizes.iter().sum::<usize>() as f64 / count as f64,
+            p50: nearest_rank(&sizes, 0.50),
+            p95: nearest_rank(&sizes, 0.95),
+            key_present_ratio: with_key as f64 / count as f64,
+            json_ratio: json as f64 / count as f64,
+        }
+    }
+}
+
+/// One produce→consume round trip measured by the latency probe
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencySample {
+    pub seq: u64,
+    pub offset: Option<i64>,
+    pub latency_ms: Option<f64>,
+    pub error: Option<String>,
+}
+
+/// Message counts of one partition number in two topics; a side is `None` when that topic
+/// doesn't have the partition
+#[derive(Debug, Clone, Serialize)]
+pub struct PartitionComparison {
+    pub partition: i32,
+    pub count_a: Option<i64>,
+    pub count_b: Option<i64>,
+    /// `count_a - count_b`, missing partitions counting as 0
+    pub difference: i64,
+}
+
+/// Records of one key found by `key_history`
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyHistory {
+    /// Partition that was scanned
+    pub partition: i32,
+    /// Records read to find the matches
+    pub scanned: usize,
+    /// Matching records, newest first
+    pub messages: Vec<ConsumedMessage>,
+}
+
+/// Topic message count seen by one poll of `wait_for_messages`
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageCountProgress {
+    /// Summed offset ranges of all partitions
+    pub count: i64,
+    pub target: i64,
+    pub elapsed_ms: u64,
+}
+
+/// Outcome of `wait_for_messages`
+#[derive(Debug, Clone, Serialize)]
+pub struct WaitForMessagesResult {
+    /// The target count was reached before the timeout
+    pub reached: bool,
+    /// Count at the last poll
+    pub count: i64,
+    pub target: i64,
+    pub elapsed_ms: u64,
+}
+
+/// Side-by-side message counts of two topics, e.g. a source and its mirror
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicComparison {
+    pub topic_a: String,
+    pub topic_b: String,
+    pub partitions_a: usize,
+    pub partitions_b: usize,
+    pub partitions: Vec<PartitionComparison>,
+    pub total_a: i64,
+    pub total_b: i64,
+    pub difference: i64,
+    /// Same partition count and the same message count on every partition
+    pub matched: bool,
+}
+
+/// Rough size and duration of consuming a whole topic
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsumeEstimate {
+    pub total_messages: i64,
+    /// `total_messages` times the average key + value size of the sample
+    pub estimated_bytes: u64,
+    /// `estimated_bytes` at the throughput of the sample fetch; `None` when nothing was sampled
+    pub estimated_seconds: Option<f64>,
+    /// Records the estimate is based on
+    pub sampled: usize,
+}
+
+/// Outcome of a metadata ping
+#[derive(Debug, Clone, Serialize)]
+pub struct PingResult {
+    pub ok: bool,
+    /// Round trip of the metadata request; `None` when it failed or timed out
+    pub latency_ms: Option<f64>,
+    pub error: Option<String>,
+}
+
+/// Latency distribution over a probe run
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyReport {
+    pub samples: usize,
+    pub failures: usize,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub avg_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+}
+
+/// Snapshot of the cluster shape assembled from a single metadata request.
+///
+/// rskafka only exposes topic names and partition ids from the metadata response, so
+/// `cluster_id`, `controller_id`, partition leaders, replicas and ISR are not available and are
+/// reported as `None`/empty. `brokers` lists the configured bootstrap brokers.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClusterMetadata {
+    pub cluster_id: Option<String>,
+    pub controller_id: Option<i32>,
+    pub brokers: Vec<BrokerMetadata>,
+    pub topics: Vec<TopicMetadata>,
+}
+
+/// Result of a metadata-backed operation that may succeed while some brokers are down
+#[derive(Debug, Clone, Serialize)]
+pub struct PartialResult<T> {
+    pub data: T,
+    /// Bootstrap brokers that could not be reached
+    pub unreachable_brokers: Vec<String>,
+}
+
+/// A broker address known to the client
+#[derive(Debug, Clone, Serialize)]
+pub struct BrokerMetadata {
+    pub id: Option<i32>,
+    pub host: String,
+    pub port: Option<u16>,
+    pub rack: Option<String>,
+}
+
+/// Metadata for a single topic
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicMetadata {
+    pub name: String,
+    pub partitions: Vec<PartitionMetadata>,
+}
+
+/// Metadata for a single partition
+#[derive(Debug, Clone, Serialize)]
+pub struct PartitionMetadata {
+    pub id: i32,
+    pub leader: Option<i32>,
+    pub replicas: Vec<i32>,
+    pub isr: Vec<i32>,
+}
+
+/// Replication health of one partition. The counts and `under_replicated` are `None` while the
+/// metadata carries no replica/ISR lists (see `ClusterMetadata`).
+#[derive(Debug, Clone, Serialize)]
+pub struct PartitionHealth {
+    pub partition: i32,
+    pub under_replicated: Option<bool>,
+    pub isr_count: Option<usize>,
+    pub replica_count: Option<usize>,
+    pub leader: Option<i32>,
+}
+
 /// Errors that can occur during Kafka operations
 #[derive(Debug, thiserror::Error, Serialize)]
 pub enum KafkaError {
@@ -56,12 +929,60 @@ pub enum KafkaError {
 
     #[error("Consume failed: {0}")]
     ConsumeFailed(String),
+
+    #[error("Metadata request failed: {0}")]
+    MetadataFailed(String),
+
+    #[error("Authentication failed: {0}")]
+    AuthenticationFailed(String),
+
+    #[error("File error: {0}")]
+    FileError(String),
+
+    #[error("Unsupported operation: {0}")]
+    Unsupported(String),
+
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("{0} cancelled")]
+    Cancelled(String),
+
+    #[error(
+        "Send timed out after {timeout_secs} seconds {}",
+        describe_send_timeout(*.produce_attempted)
+    )]
+    SendTimeout {
+        timeout_secs: u64,
+        /// Whether the produce request had been sent when the timeout hit
+        produce_attempted: bool,
+    },
 }
 
+/// Record header carrying the sequence number written by `produce_ordered`
+const SEQUENCE_HEADER: &str = "seq";
+
+/// Record header tying the records of one `reliability_test` run together
+const RELIABILITY_RUN_HEADER: &str = "reliability-run";
+
+/// Most records one `reliability_test` run produces, bounding its per-record bookkeeping
+const MAX_RELIABILITY_COUNT: usize = 100_000;
+
+/// Record header marking the probe record written by `can_produce`
+const ACL_PROBE_HEADER: &str = "acl-probe";
+
+/// Record header carrying the client-generated key used for idempotent sends
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Produce sessions unused for this long are closed the next time sessions are looked up
+const PRODUCE_SESSION_IDLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(600);
+
 /// Custom certificate verifier that skips verification (insecure, for testing only)
+#[cfg(feature = "dangerous-tls")]
 #[derive(Debug)]
 struct NoVerifier;
 
+#[cfg(feature = "dangerous-tls")]
 impl rustls::client::danger::ServerCertVerifier for NoVerifier {
     fn verify_server_cert(
         &self,
@@ -99,16 +1020,77 @@ impl rustls::client::danger::ServerCertVerifier for NoVerifier {
     }
 }
 
+/// Emitted when the client was rebuilt after its connection dropped
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconnectEvent {
+    pub reason: String,
+    pub success: bool,
+    pub error: Option<String>,
+    pub timestamp: u64,
+}
+
+/// Emitted when the connection circuit breaker opens or closes
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitEvent {
+    pub open: bool,
+    /// The connection error that opened the circuit
+    pub error: Option<String>,
+    pub timestamp: u64,
+}
+
+/// Cluster metadata and when it was fetched
+type CachedMetadata = (Instant, PartialResult<ClusterMetadata>);
+
+/// A connected client tagged with `AppConfig::connection_fingerprint` of its settings
+type CachedClient = (u64, Arc<Client>);
+
 /// Kafka service for managing connections and sending messages
 #[derive(Clone)]
 pub struct KafkaService {
     config: Arc<Mutex<AppConfig>>,
+    /// Last cluster metadata snapshot and when it was fetched
+    metadata_cache: Arc<Mutex<Option<CachedMetadata>>>,
+    /// Idempotency keys of sends acknowledged by the broker during this session
+    acked_idempotency_keys: Arc<Mutex<HashSet<String>>>,
+    /// Remembered consume positions, persisted to disk
+    positions: Arc<Mutex<PositionStore>>,
+    /// Connected client and the fingerprint of the connection settings it was built with
+    cached_client: Arc<Mutex<Option<CachedClient>>>,
+    /// Single-broker clients used to probe each bootstrap broker, by address
+    broker_probes: Arc<Mutex<HashMap<String, CachedClient>>>,
+    /// Broadcasts client rebuilds after dropped connections
+    reconnect_events: broadcast::Sender<ReconnectEvent>,
+    /// Open chunked produce sessions by id
+    produce_sessions: Arc<Mutex<HashMap<String, ProduceSession>>>,
+    /// Messages spooled while the broker was unreachable, persisted to disk
+    offline_queue: Arc<Mutex<OfflineQueue>>,
+    /// Backend used instead of the broker in `in_memory` mode
+    memory: InMemoryBackend,
+    /// Fails connection attempts fast after repeated failures
+    circuit: Arc<Mutex<CircuitBreaker>>,
+    /// Broadcasts circuit breaker state changes
+    circuit_events: broadcast::Sender<CircuitEvent>,
+    /// Cancellable long-running operations in flight
+    operations: Arc<OperationRegistry>,
 }
 
 impl KafkaService {
-    pub fn new(config: AppConfig) -> Self {
+    pub fn new(config: AppConfig, positions: PositionStore, offline_queue: OfflineQueue) -> Self {
+        let config = Arc::new(Mutex::new(config));
         Self {
-            config: Arc::new(Mutex::new(config)),
+            memory: InMemoryBackend::new(Arc::clone(&config)),
+            config,
+            metadata_cache: Arc::new(Mutex::new(None)),
+            acked_idempotency_keys: Arc::new(Mutex::new(HashSet::new())),
+            positions: Arc::new(Mutex::new(positions)),
+            offline_queue: Arc::new(Mutex::new(offline_queue)),
+            cached_client: Arc::new(Mutex::new(None)),
+            broker_probes: Arc::new(Mutex::new(HashMap::new())),
+            reconnect_events: broadcast::channel(16).0,
+            produce_sessions: Arc::new(Mutex::new(HashMap::new())),
+            circuit: Arc::new(Mutex::new(CircuitBreaker::default())),
+            circuit_events: broadcast::channel(16).0,
+            operations: OperationRegistry::new(),
         }
     }
 
@@ -117,27 +1099,159 @@ impl KafkaService {
         self.clone()
     }
 
+    /// Replace the config, dropping the cached client and metadata only when connection
+    /// settings changed so topic-only edits keep the connection alive
     pub async fn update_config(&self, config: AppConfig) {
         let mut current = self.config.lock().await;
+        let connection_changed =
+            current.connection_fingerprint() != config.connection_fingerprint();
         *current = config;
+        if connection_changed {
+            // The cached client, metadata and produce sessions may belong to a different
+            // cluster now
+            self.cached_client.lock().await.take();
+            self.metadata_cache.lock().await.take();
+            self.produce_sessions.lock().await.clear();
+        }
     }
 
     pub async fn get_config(&self) -> AppConfig {
         self.config.lock().await.clone()
     }
 
-    /// Build a configured ClientBuilder with TLS and SASL based on security settings
-    fn build_client_builder(config: &AppConfig) -> Result<ClientBuilder, KafkaError> {
-        // Support comma-separated broker addresses
-        let brokers: Vec<String> = config.broker
+    /// Overrides configured for `topic`
+    pub async fn topic_settings(&self, topic: &str) -> TopicSettings {
+        self.config.lock().await.topic_settings(topic)
+    }
+
+    /// Set (or with `None` remove) the overrides for `topic` and persist the config
+    pub async fn set_topic_override(
+        &self,
+        topic: String,
+        settings: Option<TopicSettings>,
+    ) -> Result<(), KafkaError> {
+        let config = {
+            let mut config = self.config.lock().await;
+            match settings {
+                Some(settings) => config.topic_overrides.insert(topic, settings),
+                None => config.topic_overrides.remove(&topic),
+            };
+            config.clone()
+        };
+        config
+            .save()
+            .map_err(|e| KafkaError::FileError(e.to_string()))
+    }
+
+    /// Move the app's persisted files to `dir` and reload the config, consume positions and
+    /// offline queue from there, dropping the cached client, returning the loaded config
+    pub async fn set_config_dir(&self, dir: PathBuf) -> Result<AppConfig, KafkaError> {
+        crate::config::set_config_dir(dir).map_err(|e| KafkaError::FileError(e.to_string()))?;
+
+        *self.positions.lock().await = PositionStore::load();
+        *self.offline_queue.lock().await = OfflineQueue::load();
+        self.update_config(AppConfig::load()).await;
+        // Dropped even if the loaded config happens to match the old connection settings
+        self.cached_client.lock().await.take();
+        self.metadata_cache.lock().await.take();
+        self.produce_sessions.lock().await.clear();
+        Ok(self.get_config().await)
+    }
+
+    /// Delete the saved config and consume positions and go back to `AppConfig::default()`,
+    /// dropping the cached client, returning the new config.
+    ///
+    /// The offline queue is kept: it holds messages that were never delivered.
+    pub async fn reset_config(&self) -> Result<AppConfig, KafkaError> {
+        let file_error = |e: ConfigError| KafkaError::FileError(e.to_string());
+        AppConfig::delete_saved().map_err(file_error)?;
+        PositionStore::delete_saved().map_err(file_error)?;
+
+        *self.positions.lock().await = PositionStore::default();
+        self.update_config(AppConfig::default()).await;
+        // Dropped even if the defaults happen to match the old connection settings
+        self.cached_client.lock().await.take();
+        self.metadata_cache.lock().await.take();
+        self.produce_sessions.lock().await.clear();
+        Ok(self.get_config().await)
+    }
+
+    /// Switch to new SASL credentials, persist them and test a fresh connection.
+    ///
+    /// Changing the credentials drops the cached client. If the test fails and
+    /// `rollback_on_failure` is set, the previous credentials are restored and persisted again.
+    /// Rejected while `sasl_credentials_path` is set, since the file's credentials take precedence.
+    pub async fn rotate_credentials(
+        &self,
+        username: String,
+        password: String,
+        rollback_on_failure: bool,
+    ) -> Result<CredentialRotationResult, KafkaError> {
+        let previous = self.get_config().await;
+        if let Some(path) = previous.sasl_credentials_file() {
+            return Err(KafkaError::InvalidConfig(format!(
+                "SASL credentials are read from '{}'; update that file instead of rotating them \
+                 here",
+                path.display()
+            )));
+        }
+        let rotated = AppConfig {
+            sasl_username: username,
+            sasl_password: password,
+            ..previous.clone()
+        };
+
+        // Persisted first, so a failed save leaves the running config untouched
+        rotated
+            .save()
+            .map_err(|e| KafkaError::FileError(e.to_string()))?;
+        self.update_config(rotated).await;
+
+        let error = match self.test_connection(10).await {
+            Ok(_) => None,
+            Err(e) => Some(e.to_string()),
+        };
+
+        let rolled_back = error.is_some() && rollback_on_failure;
+        if rolled_back {
+            self.update_config(previous.clone()).await;
+            previous
+                .save()
+                .map_err(|e| KafkaError::FileError(e.to_string()))?;
+        }
+
+        Ok(CredentialRotationResult {
+            connected: error.is_none(),
+            rolled_back,
+            error,
+        })
+    }
+
+    /// Split the comma-separated broker setting into individual addresses
+    fn bootstrap_brokers(config: &AppConfig) -> Vec<String> {
+        config
+            .broker
             .split(',')
             .map(|s| s.trim().to_string())
             .filter(|s| !s.is_empty())
-            .collect();
+            .collect()
+    }
+
+    /// Build a configured ClientBuilder with TLS and SASL based on security settings
+    fn build_client_builder(config: &AppConfig) -> Result<ClientBuilder, KafkaError> {
+        // Support comma-separated broker addresses
+        let brokers = Self::bootstrap_brokers(config);
         if brokers.is_empty() {
-            return Err(KafkaError::InvalidConfig("No broker addresses provided".to_string()));
+            return Err(KafkaError::InvalidConfig(
+                "No broker addresses provided".to_string(),
+            ));
         }
         let mut builder = ClientBuilder::new(brokers);
+        // Sent with every request, so brokers' logs and quotas see the configured id
+        let client_id = config.client_id.trim();
+        if !client_id.is_empty() {
+            builder = builder.client_id(client_id);
+        }
 
         // Configure TLS if needed
         match config.security_protocol {
@@ -151,16 +1265,21 @@ impl KafkaService {
         // Configure SASL if needed
         match config.security_protocol {
             SecurityProtocol::SaslPlaintext | SecurityProtocol::SaslSsl => {
-                if config.sasl_username.is_empty() {
-                    return Err(KafkaError::InvalidConfig(
-                        "SASL username is required".to_string(),
-                    ));
-                }
-                let credentials = Credentials::new(
-                    config.sasl_username.clone(),
-                    config.sasl_password.clone(),
-                );
-                let sasl = match config.sasl_mechanism {
+                let credentials = match config.sasl_credentials_file() {
+                    Some(path) => {
+                        let file = load_sasl_credentials(&path)?;
+                        Credentials::new(file.username, file.password)
+                    }
+                    None => {
+                        if config.sasl_username.is_empty() {
+                            return Err(KafkaError::InvalidConfig(
+                                "SASL username is required".to_string(),
+                            ));
+                        }
+                        Credentials::new(config.sasl_username.clone(), config.sasl_password.clone())
+                    }
+                };
+                let sasl = match config.sasl_mechanism {
                     SaslMechanism::Plain => SaslConfig::Plain(credentials),
                     SaslMechanism::ScramSha256 => SaslConfig::ScramSha256(credentials),
                     SaslMechanism::ScramSha512 => SaslConfig::ScramSha512(credentials),
@@ -170,64 +1289,449 @@ impl KafkaService {
             _ => {}
         }
 
+        // Tunnel broker connections through a proxy if configured
+        if let Some(proxy_url) = config
+            .proxy_url
+            .as_deref()
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+        {
+            builder = builder.socks5_proxy(Self::parse_proxy_url(proxy_url)?);
+        }
+
         Ok(builder)
     }
 
+    /// Turn `socks5://host:port` (or a bare `host:port`) into the address rskafka's SOCKS5
+    /// transport expects.
+    ///
+    /// rskafka only supports unauthenticated SOCKS5 tunnels, so HTTP CONNECT proxies and proxy
+    /// credentials are rejected as invalid configuration.
+    fn parse_proxy_url(url: &str) -> Result<String, KafkaError> {
+        let address = match url.split_once("://") {
+            Some((scheme, rest)) => match scheme.to_ascii_lowercase().as_str() {
+                "socks5" | "socks5h" => rest,
+                "http" | "https" => return Err(KafkaError::InvalidConfig(
+                    "HTTP CONNECT proxies are not supported by the Kafka transport; use a SOCKS5 \
+                         proxy"
+                        .to_string(),
+                )),
+                other => {
+                    return Err(KafkaError::InvalidConfig(format!(
+                        "Unsupported proxy scheme '{}'",
+                        other
+                    )))
+                }
+            },
+            None => url,
+        };
+        let address = address.trim_end_matches('/');
+
+        if address.contains('@') {
+            return Err(KafkaError::InvalidConfig(
+                "Proxy authentication is not supported".to_string(),
+            ));
+        }
+        if address
+            .rsplit_once(':')
+            .and_then(|(_, port)| port.parse::<u16>().ok())
+            .is_none()
+        {
+            return Err(KafkaError::InvalidConfig(format!(
+                "Proxy address '{}' must be host:port",
+                address
+            )));
+        }
+
+        Ok(address.to_string())
+    }
+
+    /// Build the client, reporting SASL/authorization rejections as `AuthenticationFailed`.
+    ///
+    /// rskafka retries failed broker connections indefinitely by default, which turns rejected
+    /// credentials into a bare timeout. Retries are therefore given a deadline just short of the
+    /// operation timeout so the underlying cause surfaces instead.
+    ///
+    /// A `ConnectionFailed` build is retried `connect_retries` times, `connect_retry_backoff_ms`
+    /// apart; authentication and configuration errors are not. The attempts and the sleeps
+    /// between them share the deadline: retrying stops early once the next sleep and attempt
+    /// would overrun it, and a failure after several attempts says how many there were.
+    async fn connect(
+        builder: ClientBuilder,
+        config: &AppConfig,
+        timeout_secs: u64,
+    ) -> Result<Client, KafkaError> {
+        let attempts = config.connect_retries.saturating_add(1);
+        let retry_backoff = std::time::Duration::from_millis(config.connect_retry_backoff_ms);
+        let budget = std::time::Duration::from_secs_f64(timeout_secs as f64 * 0.75);
+        // Leave room for the sleeps; if they would take the whole budget, the early stop below
+        // cuts the retries short instead
+        let per_attempt = match budget.saturating_sub(retry_backoff * (attempts - 1)) / attempts {
+            per_attempt if per_attempt.is_zero() => budget / attempts,
+            per_attempt => per_attempt,
+        };
+        let backoff = BackoffConfig {
+            deadline: Some(per_attempt),
+            ..Default::default()
+        };
+
+        let started = Instant::now();
+        let mut builder = Some(builder);
+        let mut attempt = 1;
+        loop {
+            // The builder is consumed by `build`, so retries start from a fresh one
+            let next = match builder.take() {
+                Some(builder) => builder,
+                None => Self::build_client_builder(config)?,
+            };
+            match next.backoff_config(backoff.clone()).build().await {
+                Ok(client) => return Ok(client),
+                Err(e) => match Self::map_connect_error(e, config) {
+                    KafkaError::ConnectionFailed(reason)
+                        if attempt < attempts
+                            && started.elapsed() + retry_backoff + per_attempt <= budget =>
+                    {
+                        tracing::warn!(
+                            "Connection attempt {} of {} failed: {}",
+                            attempt,
+                            attempts,
+                            reason
+                        );
+                        attempt += 1;
+                        tokio::time::sleep(retry_backoff).await;
+                    }
+                    KafkaError::ConnectionFailed(reason) if attempt > 1 => {
+                        return Err(KafkaError::ConnectionFailed(format!(
+                            "{} (after {} attempts)",
+                            reason, attempt
+                        )));
+                    }
+                    e => return Err(e),
+                },
+            }
+        }
+    }
+
+    /// Reuse the cached client if it was built with the same connection settings, otherwise
+    /// connect and cache a new one.
+    ///
+    /// New connections go through the circuit breaker: while it is open they fail immediately
+    /// instead of waiting out the timeout against a broker that was just unreachable.
+    ///
+    /// Fails with `Unsupported` in `in_memory` mode, so operations the in-memory backend doesn't
+    /// cover report that instead of trying to reach a broker.
+    async fn client(
+        &self,
+        config: &AppConfig,
+        timeout_secs: u64,
+    ) -> Result<Arc<Client>, KafkaError> {
+        if config.in_memory {
+            return Err(KafkaError::Unsupported(
+                "This operation needs a Kafka broker and is not available in in_memory mode"
+                    .to_string(),
+            ));
+        }
+
+        let fingerprint = config.connection_fingerprint();
+        let mut cached = self.cached_client.lock().await;
+        if let Some((cached_fingerprint, client)) = cached.as_ref() {
+            if *cached_fingerprint == fingerprint {
+                return Ok(client.clone());
+            }
+        }
+
+        let builder = Self::build_client_builder(config)?;
+        let settings = CircuitSettings {
+            threshold: config.circuit_breaker_threshold,
+            window: std::time::Duration::from_secs(config.circuit_breaker_window_secs),
+            cooldown: std::time::Duration::from_secs(config.circuit_breaker_cooldown_secs),
+        };
+        if !self.circuit.lock().await.allow(&settings) {
+            return Err(KafkaError::ConnectionFailed(
+                "circuit open; broker recently unreachable".to_string(),
+            ));
+        }
+
+        let connected = Self::connect(builder, config, timeout_secs).await;
+        let change = {
+            let mut circuit = self.circuit.lock().await;
+            match &connected {
+                Ok(_) => circuit.record_success().then_some((false, None)),
+                // Rejected credentials mean the broker is reachable
+                Err(e @ (KafkaError::ConnectionFailed(_) | KafkaError::ConnectionTimeout(_))) => {
+                    circuit
+                        .record_failure(&settings)
+                        .then(|| (true, Some(e.to_string())))
+                }
+                Err(_) => None,
+            }
+        };
+        if let Some((open, error)) = change {
+            // Nobody listening is fine
+            let _ = self.circuit_events.send(CircuitEvent {
+                open,
+                error,
+                timestamp: SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+            });
+        }
+
+        let client = Arc::new(connected?);
+        *cached = Some((fingerprint, client.clone()));
+        Ok(client)
+    }
+
+    /// Subscribe to circuit breaker open/close events
+    pub fn subscribe_circuit(&self) -> broadcast::Receiver<CircuitEvent> {
+        self.circuit_events.subscribe()
+    }
+
+    /// Whether the circuit breaker is currently refusing (or probing) connections
+    pub async fn circuit_open(&self) -> bool {
+        self.circuit.lock().await.is_open()
+    }
+
+    /// Run `operation` with the cached client; if it fails because the connection was dropped,
+    /// rebuild the client and run it once more
+    async fn with_reconnect<T, F, Fut>(
+        &self,
+        config: &AppConfig,
+        timeout_secs: u64,
+        operation: F,
+    ) -> Result<T, KafkaError>
+    where
+        F: Fn(Arc<Client>) -> Fut,
+        Fut: Future<Output = Result<T, KafkaError>>,
+    {
+        let client = self.client(config, timeout_secs).await?;
+        match operation(client).await {
+            Err(e) if is_disconnect_error(&e) => {
+                let client = self.reconnect(config, timeout_secs, e.to_string()).await?;
+                operation(client).await
+            }
+            result => result,
+        }
+    }
+
+    /// Drop the cached client and connect again, reporting the outcome to reconnect subscribers.
+    /// Open produce sessions are closed, since their partition clients used the dropped
+    /// connection.
+    async fn reconnect(
+        &self,
+        config: &AppConfig,
+        timeout_secs: u64,
+        reason: String,
+    ) -> Result<Arc<Client>, KafkaError> {
+        self.cached_client.lock().await.take();
+        self.produce_sessions.lock().await.clear();
+        let result = self.client(config, timeout_secs).await;
+
+        // Nobody listening is fine
+        let _ = self.reconnect_events.send(ReconnectEvent {
+            reason,
+            success: result.is_ok(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        });
+        result
+    }
+
+    /// Subscribe to reconnect events from operations and keep-alive pings
+    pub fn subscribe_reconnects(&self) -> broadcast::Receiver<ReconnectEvent> {
+        self.reconnect_events.subscribe()
+    }
+
+    /// Cancel every long-running operation in flight (consumes, exports, imports, latency
+    /// probes), returning how many were cancelled. Cancelled consumes, exports and imports fail
+    /// with `Cancelled`.
+    pub fn cancel_all_operations(&self) -> usize {
+        self.operations.cancel_all()
+    }
+
+    /// Ping the cached client with a metadata request so idle broker connections stay open,
+    /// reconnecting if the ping fails. Does nothing until an operation has connected.
+    pub async fn keep_alive(&self) {
+        let client = match self.cached_client.lock().await.as_ref() {
+            Some((_, client)) => client.clone(),
+            None => return,
+        };
+        let config = { self.config.lock().await.clone() };
+
+        let ping =
+            tokio::time::timeout(std::time::Duration::from_secs(10), client.list_topics()).await;
+        let reason = match ping {
+            Ok(Ok(_)) => return,
+            Ok(Err(e)) => format!("Keep-alive ping failed: {}", e),
+            Err(_) => "Keep-alive ping timed out".to_string(),
+        };
+        // The outcome is reported through the reconnect event
+        let _ = tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            self.reconnect(&config, 10, reason),
+        )
+        .await;
+    }
+
+    /// Time a metadata request over the cached client, connecting first if there is none.
+    ///
+    /// Unlike `test_connection` this doesn't open a fresh connection per call, so repeated pings
+    /// are cheap and reflect the health of the connection operations actually use. Only the
+    /// request itself is timed, but `timeout_ms` bounds connecting as well. Failures are
+    /// reported in the result rather than as an error.
+    pub async fn ping_broker(&self, timeout_ms: u64) -> PingResult {
+        let config = { self.config.lock().await.clone() };
+        if config.in_memory {
+            return PingResult {
+                ok: true,
+                latency_ms: Some(0.0),
+                error: None,
+            };
+        }
+
+        let timeout = std::time::Duration::from_millis(timeout_ms);
+        let ping_future = async {
+            let client = self.client(&config, timeout.as_secs().max(1)).await?;
+            let started = Instant::now();
+            client
+                .list_topics()
+                .await
+                .map_err(|e| KafkaError::MetadataFailed(e.to_string()))?;
+            Ok::<_, KafkaError>(started.elapsed().as_secs_f64() * 1000.0)
+        };
+
+        let (latency_ms, error) = match tokio::time::timeout(timeout, ping_future).await {
+            Ok(Ok(latency_ms)) => (Some(latency_ms), None),
+            Ok(Err(e)) => (None, Some(e.to_string())),
+            Err(_) => (
+                None,
+                Some(format!("Ping timed out after {} ms", timeout_ms)),
+            ),
+        };
+        PingResult {
+            ok: error.is_none(),
+            latency_ms,
+            error,
+        }
+    }
+
+    /// Classify a client build error, making sure the SASL password is never echoed back
+    fn map_connect_error(error: rskafka::client::error::Error, config: &AppConfig) -> KafkaError {
+        let is_auth_error = matches!(
+            &error,
+            rskafka::client::error::Error::ServerError {
+                protocol_error: ProtocolError::SaslAuthenticationFailed
+                    | ProtocolError::IllegalSaslState
+                    | ProtocolError::UnsupportedSaslMechanism
+                    | ProtocolError::ClusterAuthorizationFailed
+                    | ProtocolError::TopicAuthorizationFailed,
+                ..
+            }
+        );
+
+        let mut message = error.to_string();
+        if !config.sasl_password.is_empty() {
+            message = message.replace(&config.sasl_password, "********");
+        }
+
+        // Connection-level SASL failures arrive wrapped in retry errors, so match on the message
+        if is_auth_error
+            || message.contains("Sasl handshake failed")
+            || message.contains("SaslAuthenticationFailed")
+            || message.contains("AuthorizationFailed")
+        {
+            KafkaError::AuthenticationFailed(message)
+        } else {
+            KafkaError::ConnectionFailed(message)
+        }
+    }
+
     /// Build TLS configuration from AppConfig
     fn build_tls_config(config: &AppConfig) -> Result<rustls::ClientConfig, KafkaError> {
         // Ensure ring crypto provider is installed
         let _ = rustls::crypto::ring::default_provider().install_default();
 
-        // Skip verification mode (insecure, for testing)
+        // Skip verification mode (insecure, for testing) - only compiled into `dangerous-tls`
+        // builds
         if config.ssl_skip_verification {
-            let tls_config = rustls::ClientConfig::builder()
-                .dangerous()
-                .with_custom_certificate_verifier(Arc::new(NoVerifier))
-                .with_no_client_auth();
-            return Ok(tls_config);
+            #[cfg(feature = "dangerous-tls")]
+            {
+                let tls_config = rustls::ClientConfig::builder()
+                    .dangerous()
+                    .with_custom_certificate_verifier(Arc::new(NoVerifier))
+                    .with_no_client_auth();
+                return Ok(tls_config);
+            }
+
+            #[cfg(not(feature = "dangerous-tls"))]
+            return Err(KafkaError::InvalidConfig(
+                "insecure TLS not enabled in this build".to_string(),
+            ));
         }
 
         let mut root_cert_store = rustls::RootCertStore::empty();
 
-        if !config.ssl_ca_cert_path.is_empty() {
-            // Load custom CA certificate
-            let ca_data = std::fs::read(&config.ssl_ca_cert_path)
-                .map_err(|e| KafkaError::InvalidConfig(format!("Failed to read CA cert: {}", e)))?;
-            let mut reader = BufReader::new(ca_data.as_slice());
-            let certs: Vec<CertificateDer<'static>> = rustls_pemfile::certs(&mut reader)
-                .filter_map(|r| r.ok())
-                .collect();
-            for cert in certs {
-                root_cert_store
-                    .add(cert)
-                    .map_err(|e| KafkaError::InvalidConfig(format!("Failed to add CA cert: {}", e)))?;
+        if !config.ssl_ca_cert_pem.is_empty() {
+            // Inline PEM takes precedence over the path
+            let added =
+                Self::add_pem_certs(config.ssl_ca_cert_pem.as_bytes(), &mut root_cert_store)?;
+            if added == 0 {
+                return Err(KafkaError::InvalidConfig(
+                    "Inline CA PEM contained no valid certificates".to_string(),
+                ));
+            }
+        } else if !config.ssl_ca_cert_path.is_empty() {
+            // Load custom CA certificate(s) from a bundle file or a directory
+            let added =
+                Self::load_ca_certs(Path::new(&config.ssl_ca_cert_path), &mut root_cert_store)?;
+            if added == 0 {
+                return Err(KafkaError::InvalidConfig(
+                    "CA file contained no valid certificates".to_string(),
+                ));
             }
         } else {
             // Use system native root certificates
             let native_certs = rustls_native_certs::load_native_certs();
-            for cert in native_certs.certs {
-                let _ = root_cert_store.add(cert);
+            for error in &native_certs.errors {
+                tracing::warn!("Failed to load some native root certificates: {}", error);
+            }
+            let (added, ignored) = root_cert_store.add_parsable_certificates(native_certs.certs);
+            tracing::info!(
+                "Loaded {} native root certificates ({} ignored)",
+                added,
+                ignored
+            );
+            if added == 0 {
+                return Err(KafkaError::InvalidConfig(
+                    "No trusted root certificates available; specify ssl_ca_cert_path".to_string(),
+                ));
             }
         }
 
-        let builder = rustls::ClientConfig::builder()
-            .with_root_certificates(root_cert_store);
+        let builder = rustls::ClientConfig::builder().with_root_certificates(root_cert_store);
 
-        // Add client certificate (mTLS) if provided
-        let tls_config = if !config.ssl_client_cert_path.is_empty()
-            && !config.ssl_client_key_path.is_empty()
-        {
-            let cert_data = std::fs::read(&config.ssl_client_cert_path).map_err(|e| {
-                KafkaError::InvalidConfig(format!("Failed to read client cert: {}", e))
-            })?;
+        // Add client certificate (mTLS) if provided, inline PEM first and then file paths
+        let client_cert = Self::read_pem_source(
+            &config.ssl_client_cert_pem,
+            &config.ssl_client_cert_path,
+            "client cert",
+        )?;
+        let client_key = Self::read_pem_source(
+            &config.ssl_client_key_pem,
+            &config.ssl_client_key_path,
+            "client key",
+        )?;
+
+        let tls_config = if let (Some(cert_data), Some(key_data)) = (client_cert, client_key) {
             let mut cert_reader = BufReader::new(cert_data.as_slice());
             let certs: Vec<CertificateDer<'static>> = rustls_pemfile::certs(&mut cert_reader)
                 .filter_map(|r| r.ok())
                 .collect();
 
-            let key_data = std::fs::read(&config.ssl_client_key_path).map_err(|e| {
-                KafkaError::InvalidConfig(format!("Failed to read client key: {}", e))
-            })?;
             let mut key_reader = BufReader::new(key_data.as_slice());
             let key: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut key_reader)
                 .map_err(|e| {
@@ -237,11 +1741,9 @@ impl KafkaService {
                     KafkaError::InvalidConfig("No private key found in key file".to_string())
                 })?;
 
-            builder
-                .with_client_auth_cert(certs, key)
-                .map_err(|e| {
-                    KafkaError::InvalidConfig(format!("Failed to configure client auth: {}", e))
-                })?
+            builder.with_client_auth_cert(certs, key).map_err(|e| {
+                KafkaError::InvalidConfig(format!("Failed to configure client auth: {}", e))
+            })?
         } else {
             builder.with_no_client_auth()
         };
@@ -249,206 +1751,3622 @@ impl KafkaService {
         Ok(tls_config)
     }
 
-    /// Test connection to the Kafka broker with timeout
+    /// Get PEM bytes from an inline config value, falling back to reading `path`.
+    ///
+    /// Returns `None` when neither is set.
+    fn read_pem_source(
+        inline: &str,
+        path: &str,
+        what: &str,
+    ) -> Result<Option<Vec<u8>>, KafkaError> {
+        if !inline.is_empty() {
+            return Ok(Some(inline.as_bytes().to_vec()));
+        }
+        if path.is_empty() {
+            return Ok(None);
+        }
+        std::fs::read(path)
+            .map(Some)
+            .map_err(|e| KafkaError::InvalidConfig(format!("Failed to read {}: {}", what, e)))
+    }
+
+    /// Add every certificate in a PEM buffer to the root store, returning how many were added
+    fn add_pem_certs(
+        pem: &[u8],
+        root_cert_store: &mut rustls::RootCertStore,
+    ) -> Result<usize, KafkaError> {
+        let mut reader = BufReader::new(pem);
+        let certs: Vec<CertificateDer<'static>> = rustls_pemfile::certs(&mut reader)
+            .filter_map(|r| r.ok())
+            .collect();
+
+        let mut added = 0;
+        for cert in certs {
+            root_cert_store
+                .add(cert)
+                .map_err(|e| KafkaError::InvalidConfig(format!("Failed to add CA cert: {}", e)))?;
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    /// Add every PEM certificate found at `path` to the root store, returning how many were added.
+    ///
+    /// `path` may be a single (possibly concatenated) PEM bundle or a directory, in which case
+    /// every `*.pem` / `*.crt` file inside it is loaded.
+    fn load_ca_certs(
+        path: &Path,
+        root_cert_store: &mut rustls::RootCertStore,
+    ) -> Result<usize, KafkaError> {
+        let files: Vec<PathBuf> = if path.is_dir() {
+            let entries = std::fs::read_dir(path).map_err(|e| {
+                KafkaError::InvalidConfig(format!("Failed to read CA directory: {}", e))
+            })?;
+            let mut files: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|file| {
+                    file.is_file()
+                        && file
+                            .extension()
+                            .and_then(|ext| ext.to_str())
+                            .is_some_and(|ext| {
+                                ext.eq_ignore_ascii_case("pem") || ext.eq_ignore_ascii_case("crt")
+                            })
+                })
+                .collect();
+            files.sort();
+            files
+        } else {
+            vec![path.to_path_buf()]
+        };
+
+        let mut added = 0;
+        for file in files {
+            let ca_data = std::fs::read(&file).map_err(|e| {
+                KafkaError::InvalidConfig(format!(
+                    "Failed to read CA cert {}: {}",
+                    file.display(),
+                    e
+                ))
+            })?;
+            added += Self::add_pem_certs(&ca_data, root_cert_store)?;
+        }
+
+        Ok(added)
+    }
+
+    /// Test connection to the Kafka broker with timeout.
+    ///
+    /// Deliberately bypasses the circuit breaker: an explicit test should reach the broker even
+    /// while the circuit is open, and it builds a throwaway client rather than the cached one, so
+    /// its outcome isn't recorded either.
     pub async fn test_connection(&self, timeout_secs: u64) -> Result<bool, KafkaError> {
         // Clone config and release lock BEFORE async operation
-        let config = {
-            self.config.lock().await.clone()
-        };
+        let config = { self.config.lock().await.clone() };
+        if config.in_memory {
+            return KafkaBackend::test_connection(&self.memory, timeout_secs).await;
+        }
 
         let builder = Self::build_client_builder(&config)?;
-        let connect_future = builder.build();
+        let connect_future = Self::connect(builder, &config, timeout_secs);
 
-        match tokio::time::timeout(
-            std::time::Duration::from_secs(timeout_secs),
-            connect_future
-        ).await {
+        match tokio::time::timeout(std::time::Duration::from_secs(timeout_secs), connect_future)
+            .await
+        {
             Ok(Ok(_)) => Ok(true),
-            Ok(Err(e)) => Err(KafkaError::ConnectionFailed(e.to_string())),
+            Ok(Err(e)) => Err(e),
             Err(_) => Err(KafkaError::ConnectionTimeout(timeout_secs)),
         }
     }
 
-    /// Send a message to the configured topic with timeout
-    pub async fn send_message(&self, message: String) -> Result<SendResult, KafkaError> {
-        // Clone config and release lock BEFORE async operations
-        let config = {
-            self.config.lock().await.clone()
+    /// Look up the number of partitions of a topic from cluster metadata
+    async fn partition_count(client: &Client, topic: &str) -> Result<i32, KafkaError> {
+        let topics = client
+            .list_topics()
+            .await
+            .map_err(|e| KafkaError::ConnectionFailed(e.to_string()))?;
+
+        topics
+            .into_iter()
+            .find(|t| t.name == topic)
+            .map(|t| t.partitions.len() as i32)
+            .filter(|&count| count > 0)
+            .ok_or_else(|| KafkaError::SendFailed(format!("Topic '{}' not found", topic)))
+    }
+
+    /// Send a message to the configured topic with timeout.
+    ///
+    /// Keyed messages are routed with the Java client's murmur2 partitioner so they land on the
+    /// same partition as records produced by other clients; unkeyed messages go to the topic's
+    /// `default_partition` (0 unless overridden).
+    /// `options.compression` overrides the configured default codec for this call only.
+    ///
+    /// In `idempotent` mode the record carries an `idempotency-key` header (the caller's
+    /// `idempotency_key`, or a generated UUID) and a send whose key was already acknowledged in
+    /// this session is skipped. This is app-level de-duplication only: it does not survive an app
+    /// restart, does not cover two sends racing with the same key, and cannot detect a record that
+    /// reached the broker but whose ack was lost unless the caller retries with the same key.
+    ///
+    /// `options.key_encoding` says how `key` is turned into bytes (UTF-8, base64-decoded, or
+    /// JSON-validated UTF-8); partitioning hashes the resulting bytes.
+    ///
+    /// An empty `key` is sent as a zero-length key rather than no key, and other keys keep their
+    /// exact bytes. The configured `default_key` (always UTF-8) is used when `key` is `None`, and
+    /// `default_headers` are added to the record under `options.headers`; the `idempotency-key`
+    /// header always wins. `options.partition` picks the partition outright and fails with a
+    /// `SendFailed` naming it if the topic has no such partition; otherwise keyed
+    /// records are hashed and keyless ones go to the topic's default partition (0 unless
+    /// overridden).
+    ///
+    /// `options.timestamp_ms` sets the record's CreateTime. A topic with
+    /// `message.timestamp.type=LogAppendTime` replaces it with the broker's append time; see
+    /// `SendResult::timestamp_overridden_by_broker` for why that can't be detected yet.
+    ///
+    /// With `enable_offline_queue`, a send that fails because the broker is unreachable (before
+    /// the produce request went out) is spooled instead and reported with `queued_offline`.
+    ///
+    /// With `fire_and_forget`, the call returns as soon as the partition is resolved and the
+    /// produce runs in the background; rskafka always requests acks, so this only stops the call
+    /// from blocking on them. Delivery isn't guaranteed and produce errors are only logged.
+    ///
+    /// The send must finish within `produce_timeout_ms`. On timeout a `SendTimeout` error says
+    /// whether the produce request had gone out: if not, nothing was written; if so, the record
+    /// may or may not have been. `acks` must be `All`, the only level rskafka requests.
+    ///
+    /// A leader change (`NotLeaderOrFollower`, e.g. during a broker restart) needs no handling
+    /// here: rskafka's partition client refreshes the leader and retries the produce itself until
+    /// its backoff deadline, which `connect` keeps inside the operation timeout. To reproduce
+    /// manually, send in a loop against a multi-broker cluster and run
+    /// `kafka-leader-election.sh --election-type preferred` or restart the partition's leader.
+    ///
+    /// With `options.repeat`, see `send_repeated`.
+    pub async fn send_message(
+        &self,
+        message: String,
+        key: Option<String>,
+        options: SendOptions,
+    ) -> Result<SendResult, KafkaError> {
+        match options.repeat {
+            Some(repeat) if repeat != 1 => self.send_repeated(message, key, options, repeat).await,
+            _ => self.send_once(message, key, options).await,
+        }
+    }
+
+    /// Send the message `repeat` times over the cached client, one `send_message` at a time.
+    ///
+    /// A failed copy is counted and the loop goes on, unless the broker became unreachable (or
+    /// the copy was queued offline), in which case it stops early. Each copy gets its own
+    /// idempotency key, since a shared one would make every copy after the first a skipped
+    /// duplicate, so a caller-supplied `idempotency_key` is rejected. `append_sequence` needs a
+    /// `Utf8` value, as the suffix would otherwise be appended to the encoded text.
+    async fn send_repeated(
+        &self,
+        message: String,
+        key: Option<String>,
+        options: SendOptions,
+        repeat: u32,
+    ) -> Result<SendResult, KafkaError> {
+        if options.idempotency_key.is_some() {
+            return Err(KafkaError::InvalidInput(
+                "idempotency_key can't be combined with repeat: every copy needs its own key"
+                    .to_string(),
+            ));
+        }
+        if options.append_sequence && options.value_encoding != Encoding::Utf8 {
+            return Err(KafkaError::InvalidInput(format!(
+                "append_sequence needs a Utf8 value, not {:?}",
+                options.value_encoding
+            )));
+        }
+        let mut summary = RepeatSummary {
+            requested: repeat,
+            succeeded: 0,
+            failed: 0,
+            stopped_early: false,
+            first_error: None,
+        };
+        let mut last_sent = None;
+        for seq in 0..repeat {
+            let message = if options.append_sequence {
+                format!("{}-{}", message, seq)
+            } else {
+                message.clone()
+            };
+            match self.send_once(message, key.clone(), options.clone()).await {
+                Ok(result) if result.success => {
+                    summary.succeeded += 1;
+                    last_sent = Some(result);
+                }
+                Ok(result) => {
+                    // Queued offline: the broker is unreachable, so stop rather than spool the rest
+                    summary.failed += 1;
+                    summary.first_error.get_or_insert(result.message);
+                    summary.stopped_early = seq + 1 < repeat;
+                    break;
+                }
+                Err(e) => {
+                    summary.failed += 1;
+                    let unreachable = is_unreachable_error(&e) || is_disconnect_error(&e);
+                    summary.first_error.get_or_insert(e.to_string());
+                    if unreachable {
+                        summary.stopped_early = seq + 1 < repeat;
+                        break;
+                    }
+                }
+            }
+        }
+
+        let compression = {
+            let config = self.config.lock().await;
+            resolve_compression(config.compression_for(&config.topic), options.compression)
         };
+        let result = last_sent.unwrap_or_else(|| SendResult {
+            success: false,
+            ..SendResult::sent(
+                summary.first_error.as_deref().unwrap_or("No copies sent"),
+                compression,
+                None,
+            )
+        });
+        Ok(SendResult {
+            message: format!("Sent {} of {} copies", summary.succeeded, repeat),
+            repeat: Some(summary),
+            ..result
+        })
+    }
+
+    async fn send_once(
+        &self,
+        message: String,
+        key: Option<String>,
+        options: SendOptions,
+    ) -> Result<SendResult, KafkaError> {
+        // Clone config and release lock BEFORE async operations
+        let config = { self.config.lock().await.clone() };
         let topic = config.topic.clone();
+        let codec = options
+            .compression
+            .unwrap_or_else(|| config.compression_for(&topic));
+        let compression = resolve_compression(codec, None);
+        let default_partition = config.topic_settings(&topic).default_partition.unwrap_or(0);
+        let partition = options.partition;
+        let (record, idempotency_key) = build_send_record(&config, message, key, options)?;
 
-        // Build client builder with security config
-        let builder = Self::build_client_builder(&config)?;
+        if let Some(idempotency_key) = &idempotency_key {
+            if self
+                .acked_idempotency_keys
+                .lock()
+                .await
+                .contains(idempotency_key)
+            {
+                return Ok(SendResult::sent(
+                    "Duplicate send skipped (idempotency key already acknowledged)",
+                    compression,
+                    Some(idempotency_key.clone()),
+                ));
+            }
+        }
+
+        if config.in_memory {
+            let (partition, offset) = self.memory.store(&topic, record, partition).await?;
+            if let Some(idempotency_key) = &idempotency_key {
+                self.acked_idempotency_keys
+                    .lock()
+                    .await
+                    .insert(idempotency_key.clone());
+            }
+            return Ok(
+                SendResult::sent("Message stored in memory", compression, idempotency_key)
+                    .at(partition, Some(offset)),
+            );
+        }
+        if config.acks == Acks::Leader {
+            return Err(KafkaError::Unsupported(
+                "acks=1 (leader only) is not supported: the Kafka client library always produces \
+                 with acks=all"
+                    .to_string(),
+            ));
+        }
+        // Whole seconds for the connection helpers and `SendTimeout`
+        let timeout_secs = config.produce_timeout_ms.div_ceil(1000).max(1);
+
+        // Set just before the produce request goes out, so a timeout can tell whether the record
+        // may have been written
+        let produce_attempted = std::sync::atomic::AtomicBool::new(false);
 
-        // Wrap entire operation in a 10 second timeout
+        // Wrap entire operation in the produce timeout
         let send_future = async {
-            // Build client
-            let client = builder
-                .build()
-                .await
-                .map_err(|e| KafkaError::ConnectionFailed(e.to_string()))?;
+            // Only the partition lookup is retried after a reconnect; retrying the produce itself
+            // could duplicate a record whose ack was lost
+            let (topic_ref, key_ref) = (topic.as_str(), record.key.as_deref());
+            let partition_client = self
+                .with_reconnect(&config, timeout_secs, move |client| async move {
+                    let partition = match (partition, key_ref) {
+                        (Some(partition), _) => {
+                            let partitions = Self::partition_count(&client, topic_ref).await?;
+                            if !(0..partitions).contains(&partition) {
+                                return Err(KafkaError::SendFailed(format!(
+                                    "Partition {} does not exist on topic '{}', which has {} \
+                                     partition(s)",
+                                    partition, topic_ref, partitions
+                                )));
+                            }
+                            partition
+                        }
+                        (None, Some(key)) => {
+                            let partitions = Self::partition_count(&client, topic_ref).await?;
+                            partition_for_key(key, partitions)
+                        }
+                        (None, None) => default_partition,
+                    };
 
-            // Get partition client for topic (partition 0)
-            let partition_client = client
-                .partition_client(&topic, 0, UnknownTopicHandling::Error)
-                .await
-                .map_err(|e| KafkaError::SendFailed(e.to_string()))?;
+                    client
+                        .partition_client(topic_ref, partition, UnknownTopicHandling::Error)
+                        .await
+                        .map_err(|e| KafkaError::SendFailed(e.to_string()))
+                })
+                .await?;
+            let partition_client = Arc::new(partition_client);
 
-            // Create record
-            let record = Record {
-                key: None,
-                value: Some(message.into_bytes()),
-                headers: Default::default(),
-                timestamp: Utc::now(),
-            };
+            if config.fire_and_forget {
+                let record = record.clone();
+                let partition = partition_client.partition();
+                tokio::spawn(async move {
+                    if let Err(e) = partition_client.produce(vec![record], compression).await {
+                        tracing::warn!("Fire-and-forget produce failed: {}", e);
+                    }
+                });
+                return Ok(SendResult::sent(
+                    "Message handed off without waiting for acknowledgement",
+                    compression,
+                    idempotency_key.clone(),
+                )
+                .at(partition, None));
+            }
 
-            // Send the record
-            partition_client
-                .produce(vec![record], Compression::NoCompression)
+            produce_attempted.store(true, std::sync::atomic::Ordering::Relaxed);
+
+            // Send the record; rskafka retries a moved leader itself
+            let offsets = partition_client
+                .produce(vec![record.clone()], compression)
                 .await
                 .map_err(|e| KafkaError::SendFailed(e.to_string()))?;
 
-            let timestamp = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
+            if let Some(idempotency_key) = &idempotency_key {
+                self.acked_idempotency_keys
+                    .lock()
+                    .await
+                    .insert(idempotency_key.clone());
+            }
 
-            Ok(SendResult {
-                success: true,
-                message: "Message sent successfully".to_string(),
-                timestamp,
-            })
+            Ok(SendResult::sent(
+                "Message sent successfully",
+                compression,
+                idempotency_key.clone(),
+            )
+            .at(partition_client.partition(), offsets.first().copied()))
         };
 
-        match tokio::time::timeout(std::time::Duration::from_secs(10), send_future).await {
+        let produce_timeout = std::time::Duration::from_millis(config.produce_timeout_ms);
+        let result = match tokio::time::timeout(produce_timeout, send_future).await {
             Ok(result) => result,
-            Err(_) => Err(KafkaError::ConnectionTimeout(10)),
+            Err(_) => Err(KafkaError::SendTimeout {
+                timeout_secs,
+                produce_attempted: produce_attempted.load(std::sync::atomic::Ordering::Relaxed),
+            }),
+        };
+
+        match result {
+            Err(e) if config.enable_offline_queue && is_unreachable_error(&e) => {
+                let depth = {
+                    let mut queue = self.offline_queue.lock().await;
+                    // Keyed records without an explicit partition are hashed again on replay
+                    let spooled_partition =
+                        partition.or(record.key.is_none().then_some(default_partition));
+                    queue.push(
+                        &topic,
+                        &record,
+                        spooled_partition,
+                        codec,
+                        idempotency_key.clone(),
+                    );
+                    queue
+                        .save()
+                        .map_err(|e| KafkaError::FileError(e.to_string()))?;
+                    queue.depth()
+                };
+                Ok(SendResult {
+                    success: false,
+                    queued_offline: true,
+                    ..SendResult::sent(
+                        &format!("{}; message queued offline ({} waiting)", e, depth),
+                        compression,
+                        idempotency_key,
+                    )
+                })
+            }
+            result => result,
         }
     }
 
-    /// Create a new topic on the Kafka broker
-    pub async fn create_topic(
-        &self,
-        topic_name: String,
-        num_partitions: i32,
-        replication_factor: i16,
-    ) -> Result<TopicCreateResult, KafkaError> {
-        let config = {
-            self.config.lock().await.clone()
-        };
-
-        let builder = Self::build_client_builder(&config)?;
+    /// Number of messages waiting in the offline queue
+    pub async fn offline_queue_depth(&self) -> usize {
+        self.offline_queue.lock().await.depth()
+    }
 
-        let create_future = async {
-            let client = builder
-                .build()
-                .await
-                .map_err(|e| KafkaError::ConnectionFailed(e.to_string()))?;
+    /// Replay the offline queue in order, to the partition and with the codec chosen when each
+    /// message was spooled; keyed messages spooled without a partition are routed like
+    /// `send_message`.
+    ///
+    /// Each message is removed from the spool file only after its produce is acknowledged, so a
+    /// failure leaves it (and everything after it) queued for the next flush. A replayed
+    /// idempotency key is recorded as acknowledged, and a message whose key already was is dropped
+    /// unsent. A produce that timed out after the request went out is dropped too rather than
+    /// replayed, since it may already have been written; the error says so.
+    pub async fn flush_offline_queue(&self) -> Result<OfflineFlushResult, KafkaError> {
+        let config = { self.config.lock().await.clone() };
+        let flushed = std::sync::atomic::AtomicUsize::new(0);
+        let skipped = std::sync::atomic::AtomicUsize::new(0);
+        // Set while the head message's produce is in flight, so a timeout can tell whether it
+        // may have been written
+        let produce_attempted = std::sync::atomic::AtomicBool::new(false);
 
-            let controller_client = client
-                .controller_client()
-                .map_err(|e| KafkaError::TopicCreateFailed(e.to_string()))?;
+        let flush_future = async {
+            let client = self.client(&config, 30).await?;
+            loop {
+                let next = self.offline_queue.lock().await.front().cloned();
+                let Some(spooled) = next else {
+                    return Ok::<(), KafkaError>(());
+                };
+                if let Some(idempotency_key) = &spooled.idempotency_key {
+                    if self
+                        .acked_idempotency_keys
+                        .lock()
+                        .await
+                        .contains(idempotency_key)
+                    {
+                        let mut queue = self.offline_queue.lock().await;
+                        queue.pop_front();
+                        queue
+                            .save()
+                            .map_err(|e| KafkaError::FileError(e.to_string()))?;
+                        skipped.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        continue;
+                    }
+                }
+                let record = spooled
+                    .record
+                    .into_record()
+                    .map_err(|e| KafkaError::FileError(format!("Spooled message: {}", e)))?;
 
-            controller_client
-                .create_topic(
-                    &topic_name,
-                    num_partitions,
-                    replication_factor,
-                    5_000,
-                )
-                .await
-                .map_err(|e| KafkaError::TopicCreateFailed(e.to_string()))?;
+                let partition = match (spooled.partition, &record.key) {
+                    (Some(partition), _) => partition,
+                    (None, Some(key)) => {
+                        let partitions = Self::partition_count(&client, &spooled.topic).await?;
+                        partition_for_key(key, partitions)
+                    }
+                    // Spooled before the partition was stored
+                    (None, None) => config
+                        .topic_settings(&spooled.topic)
+                        .default_partition
+                        .unwrap_or(0),
+                };
+                let codec = spooled
+                    .compression
+                    .unwrap_or_else(|| config.compression_for(&spooled.topic));
+                let partition_client = client
+                    .partition_client(&spooled.topic, partition, UnknownTopicHandling::Error)
+                    .await
+                    .map_err(|e| KafkaError::SendFailed(e.to_string()))?;
+                produce_attempted.store(true, std::sync::atomic::Ordering::Relaxed);
+                partition_client
+                    .produce(vec![record], resolve_compression(codec, None))
+                    .await
+                    .map_err(|e| KafkaError::SendFailed(e.to_string()))?;
 
-            Ok(TopicCreateResult {
-                success: true,
-                message: format!("Topic '{}' created successfully", topic_name),
-                topic: topic_name,
-            })
+                if let Some(idempotency_key) = spooled.idempotency_key {
+                    self.acked_idempotency_keys
+                        .lock()
+                        .await
+                        .insert(idempotency_key);
+                }
+                let mut queue = self.offline_queue.lock().await;
+                queue.pop_front();
+                queue
+                    .save()
+                    .map_err(|e| KafkaError::FileError(e.to_string()))?;
+                produce_attempted.store(false, std::sync::atomic::Ordering::Relaxed);
+                flushed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
         };
 
-        match tokio::time::timeout(std::time::Duration::from_secs(10), create_future).await {
-            Ok(result) => result,
-            Err(_) => Err(KafkaError::ConnectionTimeout(10)),
-        }
+        let error =
+            match tokio::time::timeout(std::time::Duration::from_secs(30), flush_future).await {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(e.to_string()),
+                Err(_) if produce_attempted.into_inner() => {
+                    let mut queue = self.offline_queue.lock().await;
+                    queue.pop_front();
+                    queue
+                        .save()
+                        .map_err(|e| KafkaError::FileError(e.to_string()))?;
+                    Some(format!(
+                    "{}; the message being replayed may have been written and was removed from \
+                     the queue",
+                    KafkaError::ConnectionTimeout(30)
+                ))
+                }
+                Err(_) => Some(KafkaError::ConnectionTimeout(30).to_string()),
+            };
+        Ok(OfflineFlushResult {
+            flushed: flushed.into_inner(),
+            skipped: skipped.into_inner(),
+            remaining: self.offline_queue.lock().await.depth(),
+            error,
+        })
     }
 
-    /// Consume messages from the configured topic
-    pub async fn consume_messages(
+    /// Send several messages to the configured topic over one client.
+    ///
+    /// Records are grouped into produce requests of up to `batch_size` records, flushing early
+    /// once a batch has waited a non-zero `linger_ms`; whatever is left is flushed at the end,
+    /// also when the send is cancelled, which stops adding records. At most
+    /// `max_in_flight` produce requests are outstanding at once, which bounds pressure on both the
+    /// broker and the client. Each option falls back to the configured default.
+    ///
+    /// A `batch_size` of at least `messages.len()` sends everything in a single produce request.
+    /// The whole batch must finish within `timeout_secs`, which defaults to 30 s plus a second
+    /// per 1000 messages.
+    ///
+    /// With `fire_and_forget` the produces are left running once spawned and every record is
+    /// reported as `unconfirmed`.
+    pub async fn send_messages_batch(
         &self,
-        topic: String,
-        offset: i64,
-        max_messages: i32,
-    ) -> Result<Vec<ConsumedMessage>, KafkaError> {
-        let config = {
-            self.config.lock().await.clone()
-        };
-
-        let builder = Self::build_client_builder(&config)?;
+        messages: Vec<String>,
+        max_in_flight: Option<usize>,
+        linger_ms: Option<u64>,
+        batch_size: Option<usize>,
+        timeout_secs: Option<u64>,
+    ) -> Result<BatchSendResult, KafkaError> {
+        let config = { self.config.lock().await.clone() };
+        let topic = config.topic.clone();
+        let compression = resolve_compression(config.compression_for(&topic), None);
+        let partition = config.topic_settings(&topic).default_partition.unwrap_or(0);
+        let total = messages.len();
+        let timeout_secs = timeout_secs.unwrap_or(30 + total as u64 / 1000);
+        let max_in_flight = max_in_flight
+            .unwrap_or(config.max_in_flight)
+            .clamp(1, total.max(1));
+        let mut batcher = RecordBatcher::new(
+            batch_size.unwrap_or(config.batch_size),
+            std::time::Duration::from_millis(linger_ms.unwrap_or(config.linger_ms)),
+        );
 
-        let consume_future = async {
-            let client = builder
-                .build()
-                .await
-                .map_err(|e| KafkaError::ConnectionFailed(e.to_string()))?;
+        let batch_future = async {
+            let client = self.client(&config, 30).await?;
 
-            let partition_client = client
-                .partition_client(&topic, 0, UnknownTopicHandling::Error)
-                .await
-                .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+            let partition_client = Arc::new(
+                client
+                    .partition_client(&topic, partition, UnknownTopicHandling::Error)
+                    .await
+                    .map_err(|e| KafkaError::SendFailed(e.to_string()))?,
+            );
 
-            // Query the actual available offset range
-            let earliest = partition_client
-                .get_offset(OffsetAt::Earliest)
-                .await
-                .map_err(|e| KafkaError::ConsumeFailed(format!("Failed to get earliest offset: {}", e)))?;
-            let latest = partition_client
-                .get_offset(OffsetAt::Latest)
-                .await
-                .map_err(|e| KafkaError::ConsumeFailed(format!("Failed to get latest offset: {}", e)))?;
+            let semaphore = Arc::new(Semaphore::new(max_in_flight));
+            let mut produces = JoinSet::new();
+            let fire_and_forget = config.fire_and_forget;
+            let mut spawn_produce = |records: Vec<Record>| {
+                let partition_client = Arc::clone(&partition_client);
+                let semaphore = Arc::clone(&semaphore);
+                produces.spawn(async move {
+                    let _permit = semaphore
+                        .acquire_owned()
+                        .await
+                        .map_err(|e| KafkaError::SendFailed(e.to_string()))?;
+                    partition_client
+                        .produce(records, compression)
+                        .await
+                        .map_err(|e| {
+                            if fire_and_forget {
+                                tracing::warn!("Fire-and-forget batch produce failed: {}", e);
+                            }
+                            KafkaError::SendFailed(e.to_string())
+                        })
+                });
+            };
 
-            // If partition is empty (no messages), return empty
-            if earliest >= latest {
-                return Ok(vec![]);
+            let operation = self.operations.register();
+            let mut cancelled = false;
+            for message in messages {
+                if operation.is_cancelled() {
+                    cancelled = true;
+                    break;
+                }
+                let record = Record {
+                    key: None,
+                    value: Some(message.into_bytes()),
+                    headers: config.stamped_headers(),
+                    timestamp: Utc::now(),
+                };
+                if let Some(records) = batcher.push(record) {
+                    spawn_produce(records);
+                }
+            }
+            // Records already batched are still sent when cancelled
+            if let Some(records) = batcher.flush() {
+                spawn_produce(records);
             }
 
-            // Clamp the requested offset to the valid range
-            let effective_offset = if offset < earliest {
-                earliest
-            } else if offset >= latest {
-                // No messages at or after this offset
-                return Ok(vec![]);
-            } else {
-                offset
-            };
-
-            let (records, _high_watermark) = partition_client
-                .fetch_records(
-                    effective_offset,
-                    1..1_048_576, // 1 byte to 1 MB
-                    5_000,        // 5 second max wait
-                )
-                .await
-                .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+            if fire_and_forget {
+                // Dropping the set would abort the produces; detached they run to completion
+                produces.detach_all();
+                return Ok(BatchSendResult {
+                    total,
+                    succeeded: 0,
+                    failed: 0,
+                    max_in_flight,
+                    batches: batcher.batches_flushed(),
+                    first_error: None,
+                    unconfirmed: total,
+                    partition,
+                    first_offset: None,
+                    last_offset: None,
+                });
+            }
 
-            let messages: Vec<ConsumedMessage> = records
-                .into_iter()
-                .take(max_messages as usize)
-                .map(|record| {
-                    ConsumedMessage {
-                        offset: record.offset,
-                        key: record.record.key.map(|k| String::from_utf8_lossy(&k).to_string()),
-                        value: record.record.value.map(|v| String::from_utf8_lossy(&v).to_string()),
-                        timestamp: record.record.timestamp.timestamp_millis(),
+            let mut succeeded = 0;
+            let (mut first_offset, mut last_offset): (Option<i64>, Option<i64>) = (None, None);
+            let mut first_error =
+                cancelled.then(|| KafkaError::Cancelled("Batch send".to_string()).to_string());
+            while let Some(joined) = produces.join_next().await {
+                match joined {
+                    Ok(Ok(offsets)) => {
+                        succeeded += offsets.len();
+                        if let (Some(&first), Some(&last)) = (offsets.first(), offsets.last()) {
+                            first_offset =
+                                Some(first_offset.map_or(first, |offset| offset.min(first)));
+                            last_offset = Some(last_offset.map_or(last, |offset| offset.max(last)));
+                        }
                     }
-                })
-                .collect();
+                    Ok(Err(e)) => {
+                        first_error.get_or_insert(e.to_string());
+                    }
+                    Err(e) => {
+                        first_error.get_or_insert(e.to_string());
+                    }
+                }
+            }
+
+            Ok(BatchSendResult {
+                total,
+                succeeded,
+                failed: total - succeeded,
+                max_in_flight,
+                batches: batcher.batches_flushed(),
+                first_error,
+                unconfirmed: 0,
+                partition,
+                first_offset,
+                last_offset,
+            })
+        };
+
+        match tokio::time::timeout(std::time::Duration::from_secs(timeout_secs), batch_future).await
+        {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(timeout_secs)),
+        }
+    }
+
+    /// Expand `template` (see `MessageTemplate`) `count` times, with `{{seq}}` counting from 0,
+    /// and send the results as a batch. Template errors are reported before connecting.
+    pub async fn send_templated_message(
+        &self,
+        template: String,
+        count: u32,
+    ) -> Result<BatchSendResult, KafkaError> {
+        let template = MessageTemplate::parse(&template).map_err(KafkaError::InvalidInput)?;
+        let messages = (0..count as u64).map(|seq| template.render(seq)).collect();
+        self.send_messages_batch(messages, None, None, None, None)
+            .await
+    }
+
+    /// Send the same message to the default partition of each of `topics` over one client, with
+    /// each topic's own compression override.
+    ///
+    /// A failure on one topic is recorded in its result and doesn't stop the others.
+    pub async fn send_to_topics(
+        &self,
+        topics: Vec<String>,
+        message: String,
+    ) -> Result<Vec<TopicSendResult>, KafkaError> {
+        let config = { self.config.lock().await.clone() };
+
+        let fan_out_future = async {
+            let client = self.client(&config, 30).await?;
+
+            let mut results = Vec::with_capacity(topics.len());
+            for topic in topics {
+                let produced = async {
+                    let partition = config.topic_settings(&topic).default_partition.unwrap_or(0);
+                    let compression = resolve_compression(config.compression_for(&topic), None);
+                    let partition_client = client
+                        .partition_client(&topic, partition, UnknownTopicHandling::Error)
+                        .await
+                        .map_err(|e| KafkaError::SendFailed(e.to_string()))?;
+                    let record = Record {
+                        key: None,
+                        value: Some(message.clone().into_bytes()),
+                        headers: config.stamped_headers(),
+                        timestamp: Utc::now(),
+                    };
+                    partition_client
+                        .produce(vec![record], compression)
+                        .await
+                        .map_err(|e| KafkaError::SendFailed(e.to_string()))
+                }
+                .await;
+
+                results.push(match produced {
+                    Ok(offsets) => TopicSendResult {
+                        topic,
+                        success: true,
+                        offset: offsets.first().copied(),
+                        error: None,
+                    },
+                    Err(e) => TopicSendResult {
+                        topic,
+                        success: false,
+                        offset: None,
+                        error: Some(e.to_string()),
+                    },
+                });
+            }
+            Ok(results)
+        };
+
+        match tokio::time::timeout(std::time::Duration::from_secs(30), fan_out_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(30)),
+        }
+    }
+
+    /// Open a chunked produce session on the default partition of `topic` (the configured topic
+    /// by default), returning its id for `produce_chunk` and `finish_produce`.
+    ///
+    /// A session is closed without its totals once it has been idle for
+    /// `PRODUCE_SESSION_IDLE_TIMEOUT`, or when the connection is dropped or its settings change.
+    pub async fn open_produce_session(&self, topic: Option<String>) -> Result<String, KafkaError> {
+        let config = { self.config.lock().await.clone() };
+        let topic = topic.unwrap_or_else(|| config.topic.clone());
+        let partition = config.topic_settings(&topic).default_partition.unwrap_or(0);
+
+        let open_future = async {
+            let client = self.client(&config, 10).await?;
+            client
+                .partition_client(&topic, partition, UnknownTopicHandling::Error)
+                .await
+                .map_err(|e| KafkaError::SendFailed(e.to_string()))
+        };
+        let partition_client =
+            match tokio::time::timeout(std::time::Duration::from_secs(10), open_future).await {
+                Ok(result) => result?,
+                Err(_) => return Err(KafkaError::ConnectionTimeout(10)),
+            };
+
+        let session_id = Uuid::new_v4().to_string();
+        let session = ProduceSession {
+            partition_client: Arc::new(partition_client),
+            compression: resolve_compression(config.compression_for(&topic), None),
+            headers: config.stamped_headers(),
+            summary: ProduceSessionSummary {
+                session_id: session_id.clone(),
+                topic,
+                produced: 0,
+                chunks: 0,
+                first_offset: None,
+                last_offset: None,
+            },
+            last_used: Instant::now(),
+        };
+        let mut sessions = self.produce_sessions.lock().await;
+        close_idle_sessions(&mut sessions);
+        sessions.insert(session_id.clone(), session);
+        Ok(session_id)
+    }
+
+    /// Produce `lines` as one batch in an open session. Nothing is buffered between chunks, so
+    /// memory use is bounded by the chunk size.
+    pub async fn produce_chunk(
+        &self,
+        session_id: String,
+        lines: Vec<String>,
+    ) -> Result<ProduceSessionSummary, KafkaError> {
+        let (partition_client, compression, headers) = {
+            let mut sessions = self.produce_sessions.lock().await;
+            close_idle_sessions(&mut sessions);
+            let session = sessions
+                .get(&session_id)
+                .ok_or_else(|| unknown_session(&session_id))?;
+            (
+                Arc::clone(&session.partition_client),
+                session.compression,
+                session.headers.clone(),
+            )
+        };
+
+        let records: Vec<Record> = lines
+            .into_iter()
+            .map(|line| Record {
+                key: None,
+                value: Some(line.into_bytes()),
+                headers: headers.clone(),
+                timestamp: Utc::now(),
+            })
+            .collect();
+        if records.is_empty() {
+            return self.produce_session_summary(&session_id).await;
+        }
+
+        let offsets = match tokio::time::timeout(
+            std::time::Duration::from_secs(30),
+            partition_client.produce(records, compression),
+        )
+        .await
+        {
+            Ok(result) => result.map_err(|e| KafkaError::SendFailed(e.to_string()))?,
+            Err(_) => return Err(KafkaError::ConnectionTimeout(30)),
+        };
+
+        let mut sessions = self.produce_sessions.lock().await;
+        let session = sessions
+            .get_mut(&session_id)
+            .ok_or_else(|| unknown_session(&session_id))?;
+        session.last_used = Instant::now();
+        let summary = &mut session.summary;
+        summary.produced += offsets.len();
+        summary.chunks += 1;
+        if summary.first_offset.is_none() {
+            summary.first_offset = offsets.first().copied();
+        }
+        summary.last_offset = offsets.last().copied().or(summary.last_offset);
+        Ok(summary.clone())
+    }
+
+    /// Close a produce session and return its totals
+    pub async fn finish_produce(
+        &self,
+        session_id: String,
+    ) -> Result<ProduceSessionSummary, KafkaError> {
+        self.produce_sessions
+            .lock()
+            .await
+            .remove(&session_id)
+            .map(|session| session.summary)
+            .ok_or_else(|| unknown_session(&session_id))
+    }
+
+    /// Current totals of an open session
+    async fn produce_session_summary(
+        &self,
+        session_id: &str,
+    ) -> Result<ProduceSessionSummary, KafkaError> {
+        self.produce_sessions
+            .lock()
+            .await
+            .get(session_id)
+            .map(|session| session.summary.clone())
+            .ok_or_else(|| unknown_session(session_id))
+    }
+
+    /// Create a new topic on the Kafka broker.
+    ///
+    /// rskafka doesn't expose the broker list from metadata, so a replication factor larger than
+    /// the cluster can't be rejected before the request; the broker's `InvalidReplicationFactor`
+    /// response is instead reported as an `InvalidConfig` error naming the requested factor.
+    pub async fn create_topic(
+        &self,
+        topic_name: String,
+        num_partitions: i32,
+        replication_factor: i16,
+    ) -> Result<TopicCreateResult, KafkaError> {
+        if num_partitions < 1 {
+            return Err(KafkaError::InvalidConfig(format!(
+                "Partition count must be at least 1, got {}",
+                num_partitions
+            )));
+        }
+        if replication_factor < 1 {
+            return Err(KafkaError::InvalidConfig(format!(
+                "Replication factor must be at least 1, got {}",
+                replication_factor
+            )));
+        }
+
+        let config = { self.config.lock().await.clone() };
+        if config.in_memory {
+            return KafkaBackend::create_topic(
+                &self.memory,
+                topic_name,
+                num_partitions,
+                replication_factor,
+            )
+            .await;
+        }
+
+        let create_future = async {
+            let client = self.client(&config, 10).await?;
+
+            let controller_client = client
+                .controller_client()
+                .map_err(|e| KafkaError::TopicCreateFailed(e.to_string()))?;
+
+            controller_client
+                .create_topic(&topic_name, num_partitions, replication_factor, 5_000)
+                .await
+                .map_err(|e| match &e {
+                    rskafka::client::error::Error::ServerError {
+                        protocol_error: ProtocolError::InvalidReplicationFactor,
+                        error_message,
+                        ..
+                    } => KafkaError::InvalidConfig(format!(
+                        "Replication factor {} exceeds broker count: {}",
+                        replication_factor,
+                        error_message
+                            .as_deref()
+                            .unwrap_or("not enough brokers available"),
+                    )),
+                    _ => KafkaError::TopicCreateFailed(e.to_string()),
+                })?;
+
+            // Make the new topic visible to the next metadata-backed call
+            self.metadata_cache.lock().await.take();
+
+            Ok(TopicCreateResult {
+                success: true,
+                message: format!("Topic '{}' created successfully", topic_name),
+                topic: topic_name,
+            })
+        };
+
+        match tokio::time::timeout(std::time::Duration::from_secs(10), create_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(10)),
+        }
+    }
+
+    /// Check each bootstrap broker on its own and return the ones that failed.
+    ///
+    /// Each broker gets a single-broker client that is kept for later refreshes, which only send
+    /// it a metadata request; a client that fails is dropped, so the next refresh connects again.
+    /// With a single bootstrap broker there is nothing to compare against, so an empty list is
+    /// returned and the caller's own connection decides success.
+    async fn unreachable_brokers(&self, config: &AppConfig) -> Vec<String> {
+        let brokers = Self::bootstrap_brokers(config);
+        if brokers.len() < 2 {
+            return Vec::new();
+        }
+
+        let fingerprint = config.connection_fingerprint();
+        let mut cached = {
+            let mut probes = self.broker_probes.lock().await;
+            probes.retain(|broker, (probe_fingerprint, _)| {
+                *probe_fingerprint == fingerprint && brokers.contains(broker)
+            });
+            probes.clone()
+        };
+
+        let mut probes = JoinSet::new();
+        for broker in brokers {
+            let single = AppConfig {
+                broker: broker.clone(),
+                ..config.clone()
+            };
+            let client = cached.remove(&broker).map(|(_, client)| client);
+            probes.spawn(async move {
+                let probe = async {
+                    match client {
+                        Some(client) => client.list_topics().await.ok().map(|_| client),
+                        None => {
+                            let builder = Self::build_client_builder(&single).ok()?;
+                            Self::connect(builder, &single, 5).await.ok().map(Arc::new)
+                        }
+                    }
+                };
+                let client = tokio::time::timeout(std::time::Duration::from_secs(5), probe)
+                    .await
+                    .ok()
+                    .flatten();
+                (broker, client)
+            });
+        }
+
+        let mut unreachable = Vec::new();
+        while let Some(probe) = probes.join_next().await {
+            let Ok((broker, client)) = probe else {
+                continue;
+            };
+            let mut probes = self.broker_probes.lock().await;
+            match client {
+                Some(client) => {
+                    probes.insert(broker, (fingerprint, client));
+                }
+                None => {
+                    probes.remove(&broker);
+                    unreachable.push(broker);
+                }
+            }
+        }
+        unreachable.sort();
+        unreachable
+    }
+
+    /// Compare each partition's ISR against its replicas using the (cached) cluster metadata.
+    ///
+    /// rskafka's metadata doesn't include replicas or ISR yet, so until it does every partition
+    /// reports `under_replicated: None` rather than a misleading "healthy".
+    pub async fn check_topic_health(
+        &self,
+        topic: String,
+    ) -> Result<Vec<PartitionHealth>, KafkaError> {
+        let metadata = self.get_cluster_metadata().await?;
+        let topic_metadata = metadata
+            .data
+            .topics
+            .into_iter()
+            .find(|candidate| candidate.name == topic)
+            .ok_or_else(|| KafkaError::MetadataFailed(format!("Topic '{}' not found", topic)))?;
+
+        Ok(topic_metadata
+            .partitions
+            .into_iter()
+            .map(|partition| {
+                let known = !partition.replicas.is_empty();
+                PartitionHealth {
+                    partition: partition.id,
+                    under_replicated: known
+                        .then_some(partition.isr.len() < partition.replicas.len()),
+                    isr_count: known.then_some(partition.isr.len()),
+                    replica_count: known.then_some(partition.replicas.len()),
+                    leader: partition.leader,
+                }
+            })
+            .collect())
+    }
+
+    /// Fetch the cluster shape (brokers, topics and partitions) in one metadata request.
+    ///
+    /// The client falls back across bootstrap brokers, so this succeeds as long as one of them
+    /// answers; the ones that didn't are listed in `unreachable_brokers`. Results are cached for
+    /// `metadata_cache_ttl_secs` so UI refreshes don't hit the broker.
+    pub async fn get_cluster_metadata(&self) -> Result<PartialResult<ClusterMetadata>, KafkaError> {
+        let config = { self.config.lock().await.clone() };
+        let ttl = std::time::Duration::from_secs(config.metadata_cache_ttl_secs);
+
+        if config.in_memory {
+            return Ok(PartialResult {
+                data: ClusterMetadata {
+                    cluster_id: None,
+                    controller_id: None,
+                    brokers: vec![],
+                    topics: self.memory.broker().await.topic_metadata(),
+                },
+                unreachable_brokers: vec![],
+            });
+        }
+
+        if let Some((fetched_at, metadata)) = self.metadata_cache.lock().await.as_ref() {
+            if fetched_at.elapsed() < ttl {
+                return Ok(metadata.clone());
+            }
+        }
+
+        let metadata_future = async {
+            let topics = self
+                .with_reconnect(&config, 10, |client| async move {
+                    client
+                        .list_topics()
+                        .await
+                        .map_err(|e| KafkaError::MetadataFailed(e.to_string()))
+                })
+                .await?;
+
+            let mut topics: Vec<TopicMetadata> = topics
+                .into_iter()
+                .map(|topic| TopicMetadata {
+                    name: topic.name,
+                    partitions: topic
+                        .partitions
+                        .into_iter()
+                        .map(|id| PartitionMetadata {
+                            id,
+                            leader: None,
+                            replicas: vec![],
+                            isr: vec![],
+                        })
+                        .collect(),
+                })
+                .collect();
+            topics.sort_by(|a, b| a.name.cmp(&b.name));
+
+            let brokers = Self::bootstrap_brokers(&config)
+                .into_iter()
+                .map(|address| {
+                    let (host, port) = match address.rsplit_once(':') {
+                        Some((host, port)) => (host.to_string(), port.parse().ok()),
+                        None => (address, None),
+                    };
+                    BrokerMetadata {
+                        id: None,
+                        host,
+                        port,
+                        rack: None,
+                    }
+                })
+                .collect();
+
+            Ok(ClusterMetadata {
+                cluster_id: None,
+                controller_id: None,
+                brokers,
+                topics,
+            })
+        };
+
+        let (metadata, unreachable_brokers) =
+            match tokio::time::timeout(std::time::Duration::from_secs(10), async {
+                tokio::join!(metadata_future, self.unreachable_brokers(&config))
+            })
+            .await
+            {
+                Ok((result, unreachable_brokers)) => (result?, unreachable_brokers),
+                Err(_) => return Err(KafkaError::ConnectionTimeout(10)),
+            };
+        let metadata = PartialResult {
+            data: metadata,
+            unreachable_brokers,
+        };
+
+        *self.metadata_cache.lock().await = Some((Instant::now(), metadata.clone()));
+        Ok(metadata)
+    }
+
+    /// List topic names, sorted alphabetically.
+    ///
+    /// `name_filter` is a glob (`*` and `?` wildcards) when it contains a wildcard, otherwise a
+    /// prefix. Topics starting with `_` (e.g. `_schemas`, `__consumer_offsets`) are only included
+    /// with `include_internal`; note that rskafka already omits topics the broker flags as
+    /// internal, so those can't be listed at all.
+    pub async fn list_topics(
+        &self,
+        name_filter: Option<String>,
+        include_internal: bool,
+    ) -> Result<PartialResult<Vec<String>>, KafkaError> {
+        let metadata = self.get_cluster_metadata().await?;
+
+        let mut names: Vec<String> = metadata
+            .data
+            .topics
+            .into_iter()
+            .map(|topic| topic.name)
+            .filter(|name| topic_visible(name, name_filter.as_deref(), include_internal))
+            .collect();
+        names.sort();
+        Ok(PartialResult {
+            data: names,
+            unreachable_brokers: metadata.unreachable_brokers,
+        })
+    }
+
+    /// Look up, for every partition of `topic`, the offset of the first record whose timestamp is
+    /// at or after `timestamp_ms`. Partitions without such a record report their latest offset
+    /// with `latest_fallback` set, matching Kafka's `offsetsForTimes` semantics.
+    pub async fn offsets_for_times(
+        &self,
+        topic: String,
+        timestamp_ms: i64,
+    ) -> Result<Vec<PartitionOffset>, KafkaError> {
+        let timestamp = Utc
+            .timestamp_millis_opt(timestamp_ms)
+            .single()
+            .ok_or_else(|| {
+                KafkaError::InvalidConfig(format!("Invalid timestamp {}", timestamp_ms))
+            })?;
+
+        let config = { self.config.lock().await.clone() };
+        if config.in_memory {
+            let broker = self.memory.broker().await;
+            return (0..broker.partition_count(&topic)?)
+                .map(|partition| {
+                    let (_earliest, latest) = broker.offset_range(&topic, partition)?;
+                    let found = broker.offset_for_timestamp(&topic, partition, timestamp_ms)?;
+                    Ok(PartitionOffset {
+                        partition,
+                        offset: found.unwrap_or(latest),
+                        latest_fallback: found.is_none(),
+                    })
+                })
+                .collect();
+        }
+
+        let lookup_future = async {
+            let client = self.client(&config, 15).await?;
+
+            let partitions = client
+                .list_topics()
+                .await
+                .map_err(|e| KafkaError::MetadataFailed(e.to_string()))?
+                .into_iter()
+                .find(|t| t.name == topic)
+                .map(|t| t.partitions)
+                .ok_or_else(|| KafkaError::ConsumeFailed(format!("Topic '{}' not found", topic)))?;
+
+            let mut offsets = Vec::with_capacity(partitions.len());
+            for partition in partitions {
+                let partition_client = client
+                    .partition_client(&topic, partition, UnknownTopicHandling::Error)
+                    .await
+                    .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+
+                let offset = partition_client
+                    .get_offset(OffsetAt::Timestamp(timestamp))
+                    .await
+                    .map_err(|e| {
+                        KafkaError::ConsumeFailed(format!("Failed to look up offset: {}", e))
+                    })?;
+
+                // The broker answers -1 when no record is at or after the timestamp
+                let (offset, latest_fallback) = if offset < 0 {
+                    let latest = partition_client
+                        .get_offset(OffsetAt::Latest)
+                        .await
+                        .map_err(|e| {
+                            KafkaError::ConsumeFailed(format!("Failed to get latest offset: {}", e))
+                        })?;
+                    (latest, true)
+                } else {
+                    (offset, false)
+                };
+
+                offsets.push(PartitionOffset {
+                    partition,
+                    offset,
+                    latest_fallback,
+                });
+            }
+            Ok(offsets)
+        };
+
+        match tokio::time::timeout(std::time::Duration::from_secs(15), lookup_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(15)),
+        }
+    }
+
+    /// Whether a topic's `cleanup.policy` includes `compact`, or `None` when that can't be
+    /// determined.
+    ///
+    /// Reading topic configs needs the DescribeConfigs API, which rskafka does not implement, so
+    /// after checking the topic exists this currently always reports `None` (unknown).
+    pub async fn is_topic_compacted(&self, topic: String) -> Result<Option<bool>, KafkaError> {
+        let metadata = self.get_cluster_metadata().await?;
+        if !metadata.data.topics.iter().any(|t| t.name == topic) {
+            return Err(KafkaError::MetadataFailed(format!(
+                "Topic '{}' not found",
+                topic
+            )));
+        }
+        Ok(None)
+    }
+
+    /// Write the cluster metadata (brokers, topics and partition assignments) to `path` as pretty
+    /// JSON, returning the number of topics written.
+    ///
+    /// Per-topic configs are not included: they need the DescribeConfigs API, which rskafka does
+    /// not implement.
+    pub async fn export_cluster_topology(&self, path: String) -> Result<ExportResult, KafkaError> {
+        let bootstrap_brokers = { Self::bootstrap_brokers(&*self.config.lock().await) };
+        let metadata = self.get_cluster_metadata().await?;
+        let (_, _, exported_at) = send_timestamps();
+        let count = metadata.data.topics.len();
+        let topology = ClusterTopology {
+            exported_at,
+            bootstrap_brokers,
+            unreachable_brokers: metadata.unreachable_brokers,
+            metadata: metadata.data,
+        };
+
+        let content = serde_json::to_string_pretty(&topology)
+            .map_err(|e| KafkaError::FileError(e.to_string()))?;
+        std::fs::write(&path, content).map_err(|e| KafkaError::FileError(e.to_string()))?;
+        Ok(ExportResult { path, count })
+    }
+
+    /// Check whether the configured credentials may produce to partition 0 of `topic`.
+    ///
+    /// Kafka has no dry-run produce, so this writes one record with a null key and value and an
+    /// `acl-probe` header; consumers of the topic will see it. Authorization failures are reported
+    /// as `false`, other failures as errors.
+    pub async fn can_produce(&self, topic: String) -> Result<bool, KafkaError> {
+        let config = { self.config.lock().await.clone() };
+
+        let probe_future = async {
+            let client = self.client(&config, 10).await?;
+
+            let partition_client = match client
+                .partition_client(&topic, 0, UnknownTopicHandling::Error)
+                .await
+            {
+                Ok(partition_client) => partition_client,
+                Err(e) if is_authorization_error(&e) => return Ok(false),
+                Err(e) => return Err(KafkaError::SendFailed(e.to_string())),
+            };
+
+            let record = Record {
+                key: None,
+                value: None,
+                headers: BTreeMap::from([(ACL_PROBE_HEADER.to_string(), b"true".to_vec())]),
+                timestamp: Utc::now(),
+            };
+            match partition_client
+                .produce(vec![record], Compression::NoCompression)
+                .await
+            {
+                Ok(_) => Ok(true),
+                Err(e) if is_authorization_error(&e) => Ok(false),
+                Err(e) => Err(KafkaError::SendFailed(e.to_string())),
+            }
+        };
 
-            Ok(messages)
+        match tokio::time::timeout(std::time::Duration::from_secs(10), probe_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(10)),
+        }
+    }
+
+    /// Check whether the configured credentials may read partition 0 of `topic`, by fetching at
+    /// the latest offset (which returns no data). Authorization failures are reported as `false`.
+    pub async fn can_consume(&self, topic: String) -> Result<bool, KafkaError> {
+        let config = { self.config.lock().await.clone() };
+
+        let probe_future = async {
+            let client = self.client(&config, 10).await?;
+
+            let partition_client = match client
+                .partition_client(&topic, 0, UnknownTopicHandling::Error)
+                .await
+            {
+                Ok(partition_client) => partition_client,
+                Err(e) if is_authorization_error(&e) => return Ok(false),
+                Err(e) => return Err(KafkaError::ConsumeFailed(e.to_string())),
+            };
+
+            let fetched = async {
+                let latest = partition_client.get_offset(OffsetAt::Latest).await?;
+                partition_client.fetch_records(latest, 1..1024, 0).await
+            }
+            .await;
+            match fetched {
+                Ok(_) => Ok(true),
+                Err(e) if is_authorization_error(&e) => Ok(false),
+                Err(e) => Err(KafkaError::ConsumeFailed(e.to_string())),
+            }
+        };
+
+        match tokio::time::timeout(std::time::Duration::from_secs(10), probe_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(10)),
+        }
+    }
+
+    /// Begin a transaction under `transactional_id`.
+    ///
+    /// rskafka implements neither the transactional producer APIs (InitProducerId,
+    /// AddPartitionsToTxn, EndTxn) nor idempotent producer ids, so the transaction operations
+    /// report `Unsupported` instead of producing non-transactionally.
+    pub async fn begin_transaction(&self, transactional_id: String) -> Result<(), KafkaError> {
+        Err(unsupported_api(
+            &format!("Beginning transaction '{}'", transactional_id),
+            "InitProducerId/AddPartitionsToTxn",
+        ))
+    }
+
+    /// Commit the open transaction (see `begin_transaction`)
+    pub async fn commit_transaction(&self) -> Result<(), KafkaError> {
+        Err(unsupported_api("Committing a transaction", "EndTxn"))
+    }
+
+    /// Abort the open transaction (see `begin_transaction`)
+    pub async fn abort_transaction(&self) -> Result<(), KafkaError> {
+        Err(unsupported_api("Aborting a transaction", "EndTxn"))
+    }
+
+    /// Query the (earliest, latest) offsets currently available on a partition
+    async fn offset_range(partition_client: &PartitionClient) -> Result<(i64, i64), KafkaError> {
+        let earliest = partition_client
+            .get_offset(OffsetAt::Earliest)
+            .await
+            .map_err(|e| {
+                KafkaError::ConsumeFailed(format!("Failed to get earliest offset: {}", e))
+            })?;
+        let latest = partition_client
+            .get_offset(OffsetAt::Latest)
+            .await
+            .map_err(|e| {
+                KafkaError::ConsumeFailed(format!("Failed to get latest offset: {}", e))
+            })?;
+        Ok((earliest, latest))
+    }
+
+    /// Fetch one batch of raw records starting at `offset`, clamped to the available range
+    async fn fetch_from(
+        partition_client: &PartitionClient,
+        offset: i64,
+    ) -> Result<Vec<RecordAndOffset>, KafkaError> {
+        // Query the actual available offset range
+        let (earliest, latest) = Self::offset_range(partition_client).await?;
+
+        // If partition is empty (no messages), return empty
+        if earliest >= latest {
+            return Ok(vec![]);
+        }
+
+        // Clamp the requested offset to the valid range
+        let effective_offset = if offset < earliest {
+            earliest
+        } else if offset >= latest {
+            // No messages at or after this offset
+            return Ok(vec![]);
+        } else {
+            offset
+        };
+
+        let (records, _high_watermark) = partition_client
+            .fetch_records(
+                effective_offset,
+                1..1_048_576, // 1 byte to 1 MB
+                5_000,        // 5 second max wait
+            )
+            .await
+            .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+
+        Ok(records)
+    }
+
+    /// Estimate how much data a full read of `topic` is and how long it would take.
+    ///
+    /// Message counts come from each partition's offset range, so compacted or deleted records
+    /// still count. The size and speed come from timing one fetch of recent records on the
+    /// largest partition; a full read goes through many such fetches, so treat the duration as an
+    /// order of magnitude.
+    pub async fn estimate_consume(&self, topic: String) -> Result<ConsumeEstimate, KafkaError> {
+        const SAMPLE_SIZE: i64 = 100;
+
+        let config = { self.config.lock().await.clone() };
+
+        let estimate_future = async {
+            let client = self.client(&config, 15).await?;
+            let partitions = Self::partition_count(&client, &topic).await?;
+
+            let mut total_messages = 0;
+            let mut largest: Option<(PartitionClient, i64, i64)> = None;
+            for partition in 0..partitions {
+                let partition_client = client
+                    .partition_client(&topic, partition, UnknownTopicHandling::Error)
+                    .await
+                    .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+                let (earliest, latest) = Self::offset_range(&partition_client).await?;
+                total_messages += latest - earliest;
+                if largest
+                    .as_ref()
+                    .is_none_or(|(_, e, l)| latest - earliest > l - e)
+                {
+                    largest = Some((partition_client, earliest, latest));
+                }
+            }
+
+            let (mut sampled, mut sample_bytes, mut sample_secs) = (0, 0, 0.0);
+            if let Some((partition_client, earliest, latest)) = largest.filter(|(_, e, l)| l > e) {
+                let start = (latest - SAMPLE_SIZE).max(earliest);
+                let started = Instant::now();
+                let records = Self::fetch_from(&partition_client, start).await?;
+                sample_secs = started.elapsed().as_secs_f64();
+                sampled = records.len();
+                sample_bytes = records
+                    .iter()
+                    .map(|r| {
+                        r.record.key.as_ref().map_or(0, Vec::len)
+                            + r.record.value.as_ref().map_or(0, Vec::len)
+                    })
+                    .sum::<usize>();
+            }
+
+            if sampled == 0 {
+                return Ok(ConsumeEstimate {
+                    total_messages,
+                    estimated_bytes: 0,
+                    estimated_seconds: None,
+                    sampled,
+                });
+            }
+            let avg_bytes = sample_bytes as f64 / sampled as f64;
+            Ok(ConsumeEstimate {
+                total_messages,
+                estimated_bytes: (avg_bytes * total_messages as f64) as u64,
+                estimated_seconds: Some(sample_secs / sampled as f64 * total_messages as f64),
+                sampled,
+            })
         };
 
-        match tokio::time::timeout(std::time::Duration::from_secs(15), consume_future).await {
+        match tokio::time::timeout(std::time::Duration::from_secs(15), estimate_future).await {
             Ok(result) => result,
             Err(_) => Err(KafkaError::ConnectionTimeout(15)),
         }
     }
+
+    /// Compare the per-partition message counts (latest minus earliest offset) of two topics
+    /// over one client.
+    ///
+    /// Counts are offset ranges, so they only match if both topics kept the same records:
+    /// different retention, compaction or transaction markers show up as differences too.
+    pub async fn compare_topics(
+        &self,
+        topic_a: String,
+        topic_b: String,
+    ) -> Result<TopicComparison, KafkaError> {
+        let config = { self.config.lock().await.clone() };
+
+        let compare_future = async {
+            let client = self.client(&config, 15).await?;
+            let counts_a = Self::partition_message_counts(&client, &topic_a).await?;
+            let counts_b = Self::partition_message_counts(&client, &topic_b).await?;
+
+            let partitions: Vec<PartitionComparison> = (0..counts_a.len().max(counts_b.len()))
+                .map(|index| {
+                    let (count_a, count_b) =
+                        (counts_a.get(index).copied(), counts_b.get(index).copied());
+                    PartitionComparison {
+                        partition: index as i32,
+                        count_a,
+                        count_b,
+                        difference: count_a.unwrap_or(0) - count_b.unwrap_or(0),
+                    }
+                })
+                .collect();
+            let total_a: i64 = counts_a.iter().sum();
+            let total_b: i64 = counts_b.iter().sum();
+
+            Ok(TopicComparison {
+                matched: counts_a == counts_b,
+                partitions_a: counts_a.len(),
+                partitions_b: counts_b.len(),
+                partitions,
+                total_a,
+                total_b,
+                difference: total_a - total_b,
+                topic_a: topic_a.clone(),
+                topic_b: topic_b.clone(),
+            })
+        };
+
+        match tokio::time::timeout(std::time::Duration::from_secs(15), compare_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(15)),
+        }
+    }
+
+    /// Poll `topic` once a second until its partitions' summed offset ranges reach
+    /// `target_count`, passing each poll's count to `on_progress`.
+    ///
+    /// Returns `reached: false` with the last polled count once `timeout_secs` elapse, also when
+    /// the deadline cuts a poll short; only a first poll that never completes is a timeout error.
+    /// Offset ranges count transaction markers too, so on transactional topics the target can be
+    /// reached a little early.
+    pub async fn wait_for_messages<F>(
+        &self,
+        topic: String,
+        target_count: i64,
+        timeout_secs: u64,
+        on_progress: F,
+    ) -> Result<WaitForMessagesResult, KafkaError>
+    where
+        F: Fn(MessageCountProgress) + Send + Sync,
+    {
+        const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+        let config = { self.config.lock().await.clone() };
+        let started = Instant::now();
+        let deadline = started + std::time::Duration::from_secs(timeout_secs);
+
+        let client = match tokio::time::timeout_at(
+            deadline.into(),
+            self.client(&config, timeout_secs),
+        )
+        .await
+        {
+            Ok(result) => result?,
+            Err(_) => return Err(KafkaError::ConnectionTimeout(timeout_secs)),
+        };
+
+        let mut operation = self.operations.register();
+        let mut last_count = None;
+        loop {
+            let count = match tokio::time::timeout_at(
+                deadline.into(),
+                Self::partition_message_counts(&client, &topic),
+            )
+            .await
+            {
+                Ok(counts) => counts?.iter().sum(),
+                Err(_) => match last_count {
+                    Some(count) => {
+                        return Ok(WaitForMessagesResult {
+                            reached: false,
+                            count,
+                            target: target_count,
+                            elapsed_ms: started.elapsed().as_millis() as u64,
+                        })
+                    }
+                    None => return Err(KafkaError::ConnectionTimeout(timeout_secs)),
+                },
+            };
+            last_count = Some(count);
+            let elapsed_ms = started.elapsed().as_millis() as u64;
+            on_progress(MessageCountProgress {
+                count,
+                target: target_count,
+                elapsed_ms,
+            });
+
+            let reached = count >= target_count;
+            if reached || Instant::now() + POLL_INTERVAL > deadline {
+                return Ok(WaitForMessagesResult {
+                    reached,
+                    count,
+                    target: target_count,
+                    elapsed_ms,
+                });
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = operation.cancelled() => {
+                    return Err(KafkaError::Cancelled("Wait for messages".to_string()))
+                }
+            }
+        }
+    }
+
+    /// Follow every partition of `topic` from its latest offset, passing each new message to
+    /// `on_message` as it arrives.
+    ///
+    /// Only returns on failure or when cancelled with the other operations; drop the future to
+    /// stop following.
+    pub async fn tail_messages<F>(
+        &self,
+        topic: String,
+        decode_policy: DecodePolicy,
+        on_message: F,
+    ) -> Result<(), KafkaError>
+    where
+        F: Fn(ConsumedMessage) + Send + Sync,
+    {
+        let tail = self.follow_partitions(topic, decode_policy, on_message);
+        self.operations.run("Tail", tail).await
+    }
+
+    async fn follow_partitions<F>(
+        &self,
+        topic: String,
+        decode_policy: DecodePolicy,
+        on_message: F,
+    ) -> Result<(), KafkaError>
+    where
+        F: Fn(ConsumedMessage) + Send + Sync,
+    {
+        const MEMORY_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+        const MAX_FETCH_WAIT_MS: i32 = 500;
+
+        let config = { self.config.lock().await.clone() };
+
+        if config.in_memory {
+            let mut next_offsets: HashMap<i32, i64> = HashMap::new();
+            loop {
+                let mut fetched = Vec::new();
+                {
+                    let broker = self.memory.broker().await;
+                    for partition in 0..broker.partition_count(&topic)? {
+                        let (_earliest, latest) = broker.offset_range(&topic, partition)?;
+                        let next_offset = next_offsets.entry(partition).or_insert(latest);
+                        let records = broker.fetch(&topic, partition, *next_offset, usize::MAX)?;
+                        if let Some(last) = records.last() {
+                            *next_offset = last.offset + 1;
+                        }
+                        fetched.push((partition, records));
+                    }
+                }
+                for (partition, records) in fetched {
+                    decode_records(records, partition, decode_policy, None)
+                        .messages
+                        .into_iter()
+                        .for_each(&on_message);
+                }
+                tokio::time::sleep(MEMORY_POLL_INTERVAL).await;
+            }
+        }
+
+        let setup_future = async {
+            let client = self.client(&config, 15).await?;
+            let mut partitions = Vec::new();
+            for partition in 0..Self::partition_count(&client, &topic).await? {
+                let partition_client = client
+                    .partition_client(&topic, partition, UnknownTopicHandling::Error)
+                    .await
+                    .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+                let latest = partition_client
+                    .get_offset(OffsetAt::Latest)
+                    .await
+                    .map_err(|e| {
+                        KafkaError::ConsumeFailed(format!("Failed to get latest offset: {}", e))
+                    })?;
+                partitions.push((partition, partition_client, latest));
+            }
+            Ok::<_, KafkaError>(partitions)
+        };
+        let partitions =
+            match tokio::time::timeout(std::time::Duration::from_secs(15), setup_future).await {
+                Ok(result) => result?,
+                Err(_) => return Err(KafkaError::ConnectionTimeout(15)),
+            };
+
+        // One fetch loop per partition, so a partition's records don't wait for the others' fetch
+        // waits. The loops are aborted when `fetchers` is dropped, i.e. when following stops.
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(partitions.len().max(1));
+        let mut fetchers = tokio::task::JoinSet::new();
+        for (partition, partition_client, mut next_offset) in partitions {
+            let sender = sender.clone();
+            fetchers.spawn(async move {
+                loop {
+                    let records = match partition_client
+                        .fetch_records(next_offset, 1..1_048_576, MAX_FETCH_WAIT_MS)
+                        .await
+                    {
+                        // Fetches return whole batches, which may start before the requested offset
+                        Ok((records, _high_watermark)) => records
+                            .into_iter()
+                            .filter(|record| record.offset >= next_offset)
+                            .collect::<Vec<_>>(),
+                        Err(e) => {
+                            let _ = sender
+                                .send(Err(KafkaError::ConsumeFailed(e.to_string())))
+                                .await;
+                            return;
+                        }
+                    };
+                    if let Some(last) = records.last() {
+                        next_offset = last.offset + 1;
+                    }
+                    if !records.is_empty() && sender.send(Ok((partition, records))).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        while let Some(fetched) = receiver.recv().await {
+            let (partition, records) = fetched?;
+            decode_records(records, partition, decode_policy, None)
+                .messages
+                .into_iter()
+                .for_each(&on_message);
+        }
+        Ok(())
+    }
+
+    /// Number of partitions of `topic`
+    pub async fn topic_partition_count(&self, topic: String) -> Result<i32, KafkaError> {
+        let config = { self.config.lock().await.clone() };
+        if config.in_memory {
+            return self.memory.broker().await.partition_count(&topic);
+        }
+
+        let count_future = async {
+            let client = self.client(&config, 10).await?;
+            Self::partition_count(&client, &topic).await
+        };
+        match tokio::time::timeout(std::time::Duration::from_secs(10), count_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(10)),
+        }
+    }
+
+    /// Message count (latest minus earliest offset) of every partition of `topic`, by partition
+    async fn partition_message_counts(
+        client: &Client,
+        topic: &str,
+    ) -> Result<Vec<i64>, KafkaError> {
+        let partitions = Self::partition_count(client, topic).await?;
+        let mut counts = Vec::with_capacity(partitions as usize);
+        for partition in 0..partitions {
+            let partition_client = client
+                .partition_client(topic, partition, UnknownTopicHandling::Error)
+                .await
+                .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+            let (earliest, latest) = Self::offset_range(&partition_client).await?;
+            counts.push(latest - earliest);
+        }
+        Ok(counts)
+    }
+
+    /// Guess the format of `topic`'s values from its first `sample_size` messages on partition 0
+    /// (see `TopicFormatReport::from_values` for the heuristics)
+    pub async fn detect_topic_format(
+        &self,
+        topic: String,
+        sample_size: i32,
+    ) -> Result<TopicFormatReport, KafkaError> {
+        let fetched = self
+            .consume_records(topic, 0, 0, sample_size, false, &ConsumeOptions::default())
+            .await?;
+        Ok(TopicFormatReport::from_values(
+            fetched
+                .records
+                .iter()
+                .filter_map(|r| r.record.value.as_deref()),
+        ))
+    }
+
+    /// Sample up to `sample_size` of the most recent messages on one partition and summarise
+    /// their sizes and shape
+    pub async fn sample_topic_stats(
+        &self,
+        topic: String,
+        partition: i32,
+        sample_size: i32,
+    ) -> Result<TopicStats, KafkaError> {
+        let config = { self.config.lock().await.clone() };
+        if config.in_memory {
+            let broker = self.memory.broker().await;
+            let (earliest, latest) = broker.offset_range(&topic, partition)?;
+            let start = (latest - sample_size as i64).max(earliest);
+            let records = broker.fetch(&topic, partition, start, sample_size.max(0) as usize)?;
+            return Ok(TopicStats::from_records(&records));
+        }
+
+        let stats_future = async {
+            let client = self.client(&config, 15).await?;
+
+            let partition_client = client
+                .partition_client(&topic, partition, UnknownTopicHandling::Error)
+                .await
+                .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+
+            let (earliest, latest) = Self::offset_range(&partition_client).await?;
+            let start = (latest - sample_size as i64).max(earliest);
+
+            let records: Vec<RecordAndOffset> = Self::fetch_from(&partition_client, start)
+                .await?
+                .into_iter()
+                .take(sample_size as usize)
+                .collect();
+
+            Ok(TopicStats::from_records(&records))
+        };
+
+        match tokio::time::timeout(std::time::Duration::from_secs(15), stats_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(15)),
+        }
+    }
+
+    /// Repeatedly produce a timestamped record and consume it back, measuring the round trip.
+    ///
+    /// Runs for `duration_secs` (or until cancelled), one probe every `interval_ms`, over a single
+    /// client. Each sample is passed to `on_sample` as it completes so callers can stream it to
+    /// the UI.
+    pub async fn latency_probe<F>(
+        &self,
+        topic: String,
+        duration_secs: u64,
+        interval_ms: u64,
+        on_sample: F,
+    ) -> Result<LatencyReport, KafkaError>
+    where
+        F: Fn(LatencySample) + Send + Sync,
+    {
+        let config = { self.config.lock().await.clone() };
+
+        let client = match tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            self.client(&config, 10),
+        )
+        .await
+        {
+            Ok(result) => result?,
+            Err(_) => return Err(KafkaError::ConnectionTimeout(10)),
+        };
+
+        let partition_client = client
+            .partition_client(&topic, 0, UnknownTopicHandling::Error)
+            .await
+            .map_err(|e| KafkaError::SendFailed(e.to_string()))?;
+
+        let operation = self.operations.register();
+        let run_until = Instant::now() + std::time::Duration::from_secs(duration_secs);
+        let interval = std::time::Duration::from_millis(interval_ms);
+        let mut latencies: Vec<f64> = Vec::new();
+        let mut failures = 0;
+        let mut seq = 0;
+
+        // A cancelled probe stops early and reports the samples taken so far
+        while Instant::now() < run_until && !operation.is_cancelled() {
+            let started = Instant::now();
+            let round_trip = tokio::time::timeout(
+                std::time::Duration::from_secs(5),
+                Self::probe_once(&partition_client, seq),
+            )
+            .await
+            .unwrap_or(Err(KafkaError::ConnectionTimeout(5)));
+
+            let sample = match round_trip {
+                Ok(offset) => {
+                    let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+                    latencies.push(latency_ms);
+                    LatencySample {
+                        seq,
+                        offset: Some(offset),
+                        latency_ms: Some(latency_ms),
+                        error: None,
+                    }
+                }
+                Err(e) => {
+                    failures += 1;
+                    LatencySample {
+                        seq,
+                        offset: None,
+                        latency_ms: None,
+                        error: Some(e.to_string()),
+                    }
+                }
+            };
+            on_sample(sample);
+            seq += 1;
+
+            tokio::time::sleep(interval.saturating_sub(started.elapsed())).await;
+        }
+
+        latencies.sort_by(f64::total_cmp);
+        let samples = latencies.len();
+        if samples == 0 {
+            return Ok(LatencyReport {
+                samples,
+                failures,
+                min_ms: 0.0,
+                max_ms: 0.0,
+                avg_ms: 0.0,
+                p50_ms: 0.0,
+                p95_ms: 0.0,
+                p99_ms: 0.0,
+            });
+        }
+
+        Ok(LatencyReport {
+            samples,
+            failures,
+            min_ms: latencies[0],
+            max_ms: latencies[samples - 1],
+            avg_ms: latencies.iter().sum::<f64>() / samples as f64,
+            p50_ms: nearest_rank(&latencies, 0.50),
+            p95_ms: nearest_rank(&latencies, 0.95),
+            p99_ms: nearest_rank(&latencies, 0.99),
+        })
+    }
+
+    /// Produce one probe record and wait until it can be read back, returning its offset
+    async fn probe_once(partition_client: &PartitionClient, seq: u64) -> Result<i64, KafkaError> {
+        let sent_at = Utc::now();
+        let record = Record {
+            key: None,
+            value: Some(
+                serde_json::json!({ "probe_seq": seq, "sent_at_ms": sent_at.timestamp_millis() })
+                    .to_string()
+                    .into_bytes(),
+            ),
+            headers: Default::default(),
+            timestamp: sent_at,
+        };
+
+        let offset = partition_client
+            .produce(vec![record], Compression::NoCompression)
+            .await
+            .map_err(|e| KafkaError::SendFailed(e.to_string()))?
+            .first()
+            .copied()
+            .ok_or_else(|| KafkaError::SendFailed("Broker returned no offset".to_string()))?;
+
+        loop {
+            let (records, _high_watermark) = partition_client
+                .fetch_records(offset, 1..1_048_576, 500)
+                .await
+                .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+            if records.iter().any(|r| r.offset == offset) {
+                return Ok(offset);
+            }
+        }
+    }
+
+    /// Return only the messages that arrived since the last call for this topic.
+    ///
+    /// Only partition 0 is read, since `PositionStore` keeps one position per topic.
+    ///
+    /// Reads from the stored next offset up to the current latest and then advances the stored
+    /// offset. Without a stored offset the call returns nothing and records the current latest
+    /// as the baseline; see `resume_offset` for a stored offset outside the partition's range. At
+    /// most `NEW_SINCE_LAST_LIMIT` messages are returned per call; the rest are picked up by the
+    /// next call.
+    pub async fn consume_new_since_last(
+        &self,
+        topic: String,
+    ) -> Result<Vec<ConsumedMessage>, KafkaError> {
+        const NEW_SINCE_LAST_LIMIT: usize = 1000;
+
+        let config = { self.config.lock().await.clone() };
+
+        let stored = self.positions.lock().await.next_offset(&topic);
+
+        let consume_future = async {
+            if config.in_memory {
+                let broker = self.memory.broker().await;
+                let (earliest, latest) = broker.offset_range(&topic, 0)?;
+                let start = resume_offset(stored, earliest, latest);
+                let records = broker.fetch(&topic, 0, start, NEW_SINCE_LAST_LIMIT)?;
+                let next_offset = records.last().map_or(start, |record| record.offset + 1);
+                let messages = records
+                    .into_iter()
+                    .map(|record| ConsumedMessage::from_record(record, 0))
+                    .collect();
+                return Ok((messages, next_offset));
+            }
+
+            let client = self.client(&config, 15).await?;
+
+            let partition_client = client
+                .partition_client(&topic, 0, UnknownTopicHandling::Error)
+                .await
+                .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+
+            let (earliest, latest) = Self::offset_range(&partition_client).await?;
+
+            let mut messages = Vec::new();
+            let mut next_offset = resume_offset(stored, earliest, latest);
+            while next_offset < latest && messages.len() < NEW_SINCE_LAST_LIMIT {
+                let records = Self::fetch_from(&partition_client, next_offset).await?;
+                if records.is_empty() {
+                    break;
+                }
+                for record in records {
+                    if record.offset >= latest || messages.len() >= NEW_SINCE_LAST_LIMIT {
+                        break;
+                    }
+                    next_offset = record.offset + 1;
+                    messages.push(ConsumedMessage::from_record(record, 0));
+                }
+            }
+            Ok((messages, next_offset))
+        };
+
+        let (messages, next_offset) =
+            match tokio::time::timeout(std::time::Duration::from_secs(15), consume_future).await {
+                Ok(result) => result?,
+                Err(_) => return Err(KafkaError::ConnectionTimeout(15)),
+            };
+
+        let mut positions = self.positions.lock().await;
+        positions.set_next_offset(&topic, next_offset);
+        // A failed write only loses the position across restarts; it still advances in memory
+        let _ = positions.save();
+
+        Ok(messages)
+    }
+
+    /// Consume messages from the configured topic.
+    ///
+    /// Pages through the partition until `max_messages` records are collected, the latest offset
+    /// is reached, or `options.deadline_ms` runs out (15 s by default); a deadline cut-off returns
+    /// what was collected so far, which `consume_detailed` flags as `truncated`. With
+    /// `options.max_total_bytes` collection also stops before the summed value sizes would exceed
+    /// the cap.
+    ///
+    /// With `dedup_by_key` only the highest-offset record per key is kept (null-key records are
+    /// always kept), and keys whose latest record is a tombstone are left out unless
+    /// `options.include_tombstones`, matching how a compacted topic reads. This only deduplicates
+    /// within the fetched window, not the whole topic.
+    /// Under `DecodePolicy::Strict` records that don't decode are left out; use
+    /// `consume_detailed` to see why.
+    pub async fn consume_messages(
+        &self,
+        topic: String,
+        offset: i64,
+        max_messages: i32,
+        dedup_by_key: bool,
+        decode_policy: DecodePolicy,
+        options: ConsumeOptions,
+    ) -> Result<Vec<ConsumedMessage>, KafkaError> {
+        let partition = options.partition.unwrap_or(0);
+        let fetched = self
+            .consume_records(
+                topic,
+                partition,
+                offset,
+                max_messages,
+                dedup_by_key,
+                &options,
+            )
+            .await?;
+        Ok(decode_records(
+            fetched.records,
+            partition,
+            decode_policy,
+            options.value_truncate_bytes,
+        )
+        .messages)
+    }
+
+    /// Consume like `consume_messages`, reporting records that fail to decode under
+    /// `decode_policy` in `decode_errors`
+    pub async fn consume_detailed(
+        &self,
+        topic: String,
+        offset: i64,
+        max_messages: i32,
+        dedup_by_key: bool,
+        decode_policy: DecodePolicy,
+        options: ConsumeOptions,
+    ) -> Result<DetailedConsumeResult, KafkaError> {
+        let partition = options.partition.unwrap_or(0);
+        let fetched = self
+            .consume_records(
+                topic,
+                partition,
+                offset,
+                max_messages,
+                dedup_by_key,
+                &options,
+            )
+            .await?;
+        Ok(DetailedConsumeResult {
+            truncated: fetched.truncated,
+            ..decode_records(
+                fetched.records,
+                partition,
+                decode_policy,
+                options.value_truncate_bytes,
+            )
+        })
+    }
+
+    /// Fetch the single record at `offset` on a partition.
+    ///
+    /// Returns `None` when the offset is outside the partition's current range or the record was
+    /// removed by compaction.
+    pub async fn get_message_at(
+        &self,
+        topic: String,
+        partition: i32,
+        offset: i64,
+    ) -> Result<Option<ConsumedMessage>, KafkaError> {
+        if offset < 0 {
+            return Err(KafkaError::InvalidConfig(format!(
+                "Offset must not be negative, got {}",
+                offset
+            )));
+        }
+
+        let config = { self.config.lock().await.clone() };
+        if config.in_memory {
+            let broker = self.memory.broker().await;
+            let (earliest, latest) = broker.offset_range(&topic, partition)?;
+            if offset < earliest || offset >= latest {
+                return Ok(None);
+            }
+            return Ok(broker
+                .fetch(&topic, partition, offset, 1)?
+                .into_iter()
+                .next()
+                .map(|record| ConsumedMessage::from_record(record, partition)));
+        }
+
+        let fetch_future = async {
+            let client = self.client(&config, 10).await?;
+
+            let partition_client = client
+                .partition_client(&topic, partition, UnknownTopicHandling::Error)
+                .await
+                .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+
+            let (earliest, latest) = Self::offset_range(&partition_client).await?;
+            if offset < earliest || offset >= latest {
+                return Ok(None);
+            }
+
+            let (records, _high_watermark) = partition_client
+                .fetch_records(offset, 1..1_048_576, 500)
+                .await
+                .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+
+            // After compaction the fetch starts at the next surviving offset instead
+            Ok(records
+                .into_iter()
+                .find(|record| record.offset == offset)
+                .map(|record| ConsumedMessage::from_record(record, partition)))
+        };
+
+        match tokio::time::timeout(std::time::Duration::from_secs(10), fetch_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(10)),
+        }
+    }
+
+    /// The latest `max` records with `key` on one partition of `topic`, newest first, e.g. to see
+    /// the value history of a key (or what survives compaction).
+    ///
+    /// Without `partition` the partition the key hashes to is scanned, which is where the default
+    /// partitioner puts it. Kafka can only fetch forwards, so the whole partition is read, keeping
+    /// the last `max` matches.
+    pub async fn key_history(
+        &self,
+        topic: String,
+        key: String,
+        partition: Option<i32>,
+        max: usize,
+        decode_policy: DecodePolicy,
+    ) -> Result<KeyHistory, KafkaError> {
+        let config = { self.config.lock().await.clone() };
+
+        let scan_future = async {
+            let client = self.client(&config, 60).await?;
+            let partition = match partition {
+                Some(partition) => partition,
+                None => partition_for_key(
+                    key.as_bytes(),
+                    Self::partition_count(&client, &topic).await?,
+                ),
+            };
+            let partition_client = client
+                .partition_client(&topic, partition, UnknownTopicHandling::Error)
+                .await
+                .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+
+            let (earliest, latest) = Self::offset_range(&partition_client).await?;
+            let mut matches = std::collections::VecDeque::with_capacity(max);
+            let mut scanned = 0;
+            let mut next_offset = earliest;
+            while next_offset < latest && max > 0 {
+                let records = Self::fetch_from(&partition_client, next_offset).await?;
+                if records.is_empty() {
+                    break;
+                }
+                for record in records {
+                    if record.offset >= latest {
+                        break;
+                    }
+                    next_offset = record.offset + 1;
+                    scanned += 1;
+                    if record.record.key.as_deref() == Some(key.as_bytes()) {
+                        if matches.len() == max {
+                            matches.pop_front();
+                        }
+                        matches.push_back(record);
+                    }
+                }
+            }
+
+            let mut messages =
+                decode_records(matches.into(), partition, decode_policy, None).messages;
+            messages.reverse();
+            Ok(KeyHistory {
+                partition,
+                scanned,
+                messages,
+            })
+        };
+
+        let scan_future = self.operations.run("Key history", scan_future);
+        match tokio::time::timeout(std::time::Duration::from_secs(60), scan_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(60)),
+        }
+    }
+
+    /// Produce `count` records to a single partition, each with a `seq` header counting up from 0,
+    /// for checking consumer ordering with `verify_ordered`
+    pub async fn produce_ordered(
+        &self,
+        topic: String,
+        count: usize,
+        partition: i32,
+    ) -> Result<OrderedProduceResult, KafkaError> {
+        const ORDERED_CHUNK_SIZE: usize = 500;
+
+        let config = { self.config.lock().await.clone() };
+        let compression = resolve_compression(config.compression_for(&topic), None);
+
+        let produce_future = async {
+            let client = self.client(&config, 60).await?;
+
+            let partition_client = client
+                .partition_client(&topic, partition, UnknownTopicHandling::Error)
+                .await
+                .map_err(|e| KafkaError::SendFailed(e.to_string()))?;
+
+            // Chunks are produced one after another so offsets follow the sequence
+            let mut offsets = Vec::with_capacity(count);
+            for chunk_start in (0..count).step_by(ORDERED_CHUNK_SIZE) {
+                let chunk_end = (chunk_start + ORDERED_CHUNK_SIZE).min(count);
+                let records = (chunk_start..chunk_end)
+                    .map(|seq| Record {
+                        key: None,
+                        value: Some(format!("ordered message {}", seq).into_bytes()),
+                        headers: config
+                            .stamped_headers()
+                            .into_iter()
+                            .chain([(SEQUENCE_HEADER.to_string(), seq.to_string().into_bytes())])
+                            .collect(),
+                        timestamp: Utc::now(),
+                    })
+                    .collect();
+                offsets.extend(
+                    partition_client
+                        .produce(records, compression)
+                        .await
+                        .map_err(|e| KafkaError::SendFailed(e.to_string()))?,
+                );
+            }
+
+            Ok(OrderedProduceResult {
+                partition,
+                count: offsets.len(),
+                first_offset: offsets.first().copied(),
+                last_offset: offsets.last().copied(),
+            })
+        };
+
+        match tokio::time::timeout(std::time::Duration::from_secs(60), produce_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(60)),
+        }
+    }
+
+    /// Produce `count` records numbered by a `seq` header to one partition, read the partition
+    /// back from where the run started and check every number arrived exactly once.
+    ///
+    /// The records also carry a `reliability-run` header with an id unique to the run, so records
+    /// other producers write to the partition meanwhile are ignored. Both phases use one client.
+    /// At most `MAX_RELIABILITY_COUNT` records can be tested in one run.
+    pub async fn reliability_test(
+        &self,
+        topic: String,
+        count: usize,
+        partition: i32,
+    ) -> Result<ReliabilityReport, KafkaError> {
+        const RELIABILITY_CHUNK_SIZE: usize = 500;
+
+        if count > MAX_RELIABILITY_COUNT {
+            return Err(KafkaError::InvalidInput(format!(
+                "count {} exceeds the maximum of {} records per reliability test",
+                count, MAX_RELIABILITY_COUNT
+            )));
+        }
+        let config = { self.config.lock().await.clone() };
+        let compression = resolve_compression(config.compression_for(&topic), None);
+        let run_id = Uuid::new_v4().to_string();
+
+        let test_future = async {
+            let client = self.client(&config, 60).await?;
+            let partition_client = client
+                .partition_client(&topic, partition, UnknownTopicHandling::Error)
+                .await
+                .map_err(|e| KafkaError::SendFailed(e.to_string()))?;
+
+            let (_earliest, start_offset) = Self::offset_range(&partition_client).await?;
+            let mut produced = 0;
+            for chunk_start in (0..count).step_by(RELIABILITY_CHUNK_SIZE) {
+                let chunk_end = (chunk_start + RELIABILITY_CHUNK_SIZE).min(count);
+                let records = (chunk_start..chunk_end)
+                    .map(|seq| Record {
+                        key: None,
+                        value: Some(format!("reliability message {}", seq).into_bytes()),
+                        headers: config
+                            .stamped_headers()
+                            .into_iter()
+                            .chain([
+                                (SEQUENCE_HEADER.to_string(), seq.to_string().into_bytes()),
+                                (
+                                    RELIABILITY_RUN_HEADER.to_string(),
+                                    run_id.clone().into_bytes(),
+                                ),
+                            ])
+                            .collect(),
+                        timestamp: Utc::now(),
+                    })
+                    .collect();
+                produced += partition_client
+                    .produce(records, compression)
+                    .await
+                    .map_err(|e| KafkaError::SendFailed(e.to_string()))?
+                    .len();
+            }
+
+            let (_earliest, latest) = Self::offset_range(&partition_client).await?;
+            let mut seen = vec![0usize; count];
+            let mut consumed = 0;
+            let mut next_offset = start_offset;
+            while next_offset < latest {
+                let records = Self::fetch_from(&partition_client, next_offset).await?;
+                if records.is_empty() {
+                    break;
+                }
+                for record in records {
+                    if record.offset >= latest {
+                        // Past the end of the run; stop the outer loop too, even if offsets
+                        // below `latest` (e.g. transaction markers) were never returned
+                        next_offset = latest;
+                        break;
+                    }
+                    next_offset = record.offset + 1;
+
+                    let headers = &record.record.headers;
+                    if headers.get(RELIABILITY_RUN_HEADER).map(Vec::as_slice)
+                        != Some(run_id.as_bytes())
+                    {
+                        continue;
+                    }
+                    let seq = headers
+                        .get(SEQUENCE_HEADER)
+                        .and_then(|value| std::str::from_utf8(value).ok())
+                        .and_then(|value| value.parse::<usize>().ok());
+                    if let Some(times) = seq.and_then(|seq| seen.get_mut(seq)) {
+                        *times += 1;
+                        consumed += 1;
+                    }
+                }
+            }
+
+            let numbers_where = |predicate: fn(usize) -> bool| {
+                seen.iter()
+                    .enumerate()
+                    .filter(|(_, &times)| predicate(times))
+                    .map(|(seq, _)| seq as u64)
+                    .collect()
+            };
+            Ok(ReliabilityReport {
+                partition,
+                produced,
+                consumed,
+                missing: numbers_where(|times| times == 0),
+                duplicates: numbers_where(|times| times > 1),
+            })
+        };
+
+        match tokio::time::timeout(std::time::Duration::from_secs(60), test_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(60)),
+        }
+    }
+
+    /// Read a partition from `start_offset` up to its latest offset and check that `seq` headers
+    /// increase by exactly one from record to record
+    pub async fn verify_ordered(
+        &self,
+        topic: String,
+        partition: i32,
+        start_offset: i64,
+    ) -> Result<OrderVerification, KafkaError> {
+        let config = { self.config.lock().await.clone() };
+
+        let verify_future = async {
+            let client = self.client(&config, 60).await?;
+
+            let partition_client = client
+                .partition_client(&topic, partition, UnknownTopicHandling::Error)
+                .await
+                .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+
+            let (_earliest, latest) = Self::offset_range(&partition_client).await?;
+
+            let mut verification = OrderVerification {
+                checked: 0,
+                missing_seq: 0,
+                gaps: Vec::new(),
+                reorderings: Vec::new(),
+            };
+            let mut previous_seq: Option<u64> = None;
+            let mut next_offset = start_offset;
+            while next_offset < latest {
+                let records = Self::fetch_from(&partition_client, next_offset).await?;
+                if records.is_empty() {
+                    break;
+                }
+                for record in records {
+                    if record.offset >= latest {
+                        break;
+                    }
+                    next_offset = record.offset + 1;
+
+                    let seq = record
+                        .record
+                        .headers
+                        .get(SEQUENCE_HEADER)
+                        .and_then(|value| std::str::from_utf8(value).ok())
+                        .and_then(|value| value.parse::<u64>().ok());
+                    let Some(seq) = seq else {
+                        verification.missing_seq += 1;
+                        continue;
+                    };
+
+                    verification.checked += 1;
+                    if let Some(previous) = previous_seq {
+                        let issue = SequenceIssue {
+                            offset: record.offset,
+                            previous_seq: previous,
+                            seq,
+                        };
+                        if seq <= previous {
+                            verification.reorderings.push(issue);
+                        } else if seq > previous + 1 {
+                            verification.gaps.push(issue);
+                        }
+                    }
+                    previous_seq = Some(seq);
+                }
+            }
+
+            Ok(verification)
+        };
+
+        match tokio::time::timeout(std::time::Duration::from_secs(60), verify_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(60)),
+        }
+    }
+
+    /// Write up to `max_messages` records of one partition starting at `offset` to a JSONL file,
+    /// one record per line with headers and base64 for non-UTF-8 bytes (see `ExportedRecord`).
+    /// With `options.pretty` each record is an indented document instead; `import_messages`
+    /// reads both.
+    pub async fn export_messages(
+        &self,
+        topic: String,
+        partition: i32,
+        offset: i64,
+        max_messages: usize,
+        path: String,
+        options: ExportOptions,
+    ) -> Result<ExportResult, KafkaError> {
+        let config = { self.config.lock().await.clone() };
+
+        let export_future = async {
+            let client = self.client(&config, 60).await?;
+
+            let partition_client = client
+                .partition_client(&topic, partition, UnknownTopicHandling::Error)
+                .await
+                .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+
+            let (_earliest, latest) = Self::offset_range(&partition_client).await?;
+
+            let mut lines = String::new();
+            let mut count = 0;
+            let mut next_offset = offset;
+            while next_offset < latest && count < max_messages {
+                let records = Self::fetch_from(&partition_client, next_offset).await?;
+                if records.is_empty() {
+                    break;
+                }
+                for record in records {
+                    if record.offset >= latest || count >= max_messages {
+                        break;
+                    }
+                    next_offset = record.offset + 1;
+                    let line = options
+                        .render(ExportedRecord::from_record(&record, partition))
+                        .map_err(|e| KafkaError::FileError(e.to_string()))?;
+                    lines.push_str(&line);
+                    lines.push('\n');
+                    count += 1;
+                }
+            }
+
+            std::fs::write(&path, lines).map_err(|e| KafkaError::FileError(e.to_string()))?;
+
+            Ok(ExportResult {
+                path: path.clone(),
+                count,
+            })
+        };
+
+        let export_future = self.operations.run("Export", export_future);
+        match tokio::time::timeout(std::time::Duration::from_secs(60), export_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(60)),
+        }
+    }
+
+    /// Produce the records of a JSONL export to the default partition of `topic`, restoring the
+    /// exact key, value and header bytes and the original timestamps. `stamp_client_id_header`
+    /// does not apply, so an exported `client-id` header keeps the original producer's id.
+    pub async fn import_messages(
+        &self,
+        path: String,
+        topic: String,
+    ) -> Result<ImportResult, KafkaError> {
+        const IMPORT_CHUNK_SIZE: usize = 500;
+
+        let content =
+            std::fs::read_to_string(&path).map_err(|e| KafkaError::FileError(e.to_string()))?;
+        // A stream of JSON documents, so both one-per-line and pretty-printed exports load
+        let records = serde_json::Deserializer::from_str(&content)
+            .into_iter::<ExportedRecord>()
+            .enumerate()
+            .map(|(index, record)| {
+                record
+                    .map_err(|e| format!("line {}: {}", e.line(), e))
+                    .and_then(ExportedRecord::into_record)
+                    .map_err(|e| KafkaError::FileError(format!("Record {}: {}", index + 1, e)))
+            })
+            .collect::<Result<Vec<Record>, KafkaError>>()?;
+
+        let config = { self.config.lock().await.clone() };
+        // Not stamped with `stamped_headers`, which would overwrite exported headers
+        let compression = resolve_compression(config.compression_for(&topic), None);
+        let partition = config.topic_settings(&topic).default_partition.unwrap_or(0);
+
+        let import_future = async {
+            let client = self.client(&config, 60).await?;
+
+            let partition_client = client
+                .partition_client(&topic, partition, UnknownTopicHandling::Error)
+                .await
+                .map_err(|e| KafkaError::SendFailed(e.to_string()))?;
+
+            let mut offsets = Vec::with_capacity(records.len());
+            for chunk in records.chunks(IMPORT_CHUNK_SIZE) {
+                offsets.extend(
+                    partition_client
+                        .produce(chunk.to_vec(), compression)
+                        .await
+                        .map_err(|e| KafkaError::SendFailed(e.to_string()))?,
+                );
+            }
+
+            Ok(ImportResult {
+                count: offsets.len(),
+                first_offset: offsets.first().copied(),
+                last_offset: offsets.last().copied(),
+            })
+        };
+
+        // Cancelling stops before the next chunk; chunks already produced stay written
+        let import_future = self.operations.run("Import", import_future);
+        match tokio::time::timeout(std::time::Duration::from_secs(60), import_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(60)),
+        }
+    }
+
+    /// Produce one record per data row of a CSV file to `topic`, with the value built per
+    /// `value_format` and the key taken from `key_column` when given.
+    ///
+    /// Both columns are checked against the header before anything is produced. Keyed rows are
+    /// partitioned like `send_message`, keyless rows go to the topic's default partition; each
+    /// partition is written in chunks, calling `on_progress` after every chunk.
+    pub async fn produce_from_csv<F>(
+        &self,
+        path: String,
+        topic: String,
+        key_column: Option<String>,
+        value_format: CsvValueFormat,
+        on_progress: F,
+    ) -> Result<CsvProduceResult, KafkaError>
+    where
+        F: Fn(CsvProgress) + Send + Sync,
+    {
+        const CSV_CHUNK_SIZE: usize = 500;
+
+        let content =
+            std::fs::read_to_string(&path).map_err(|e| KafkaError::FileError(e.to_string()))?;
+        let table = CsvTable::parse(&content).map_err(KafkaError::FileError)?;
+        let key_index = key_column
+            .map(|column| table.column(&column))
+            .transpose()
+            .map_err(KafkaError::FileError)?;
+        let value_index = match &value_format {
+            CsvValueFormat::SingleColumn(column) => {
+                Some(table.column(column).map_err(KafkaError::FileError)?)
+            }
+            CsvValueFormat::WholeRowJson => None,
+        };
+
+        let config = { self.config.lock().await.clone() };
+        let compression = resolve_compression(config.compression_for(&topic), None);
+        let default_partition = config.topic_settings(&topic).default_partition.unwrap_or(0);
+
+        let produce_future = async {
+            let client = self.client(&config, 60).await?;
+            let partitions = Self::partition_count(&client, &topic).await?;
+
+            let mut by_partition: BTreeMap<i32, Vec<Record>> = BTreeMap::new();
+            for row in &table.rows {
+                let value = match value_index {
+                    Some(index) => row[index].clone(),
+                    None => {
+                        let object: serde_json::Map<String, serde_json::Value> = table
+                            .headers
+                            .iter()
+                            .zip(row)
+                            .map(|(header, field)| {
+                                (header.clone(), serde_json::Value::String(field.clone()))
+                            })
+                            .collect();
+                        serde_json::Value::Object(object).to_string()
+                    }
+                };
+                let key = key_index.map(|index| row[index].clone().into_bytes());
+                let partition = match &key {
+                    Some(key) => partition_for_key(key, partitions),
+                    None => default_partition,
+                };
+                by_partition.entry(partition).or_default().push(Record {
+                    key,
+                    value: Some(value.into_bytes()),
+                    headers: config.stamped_headers(),
+                    timestamp: Utc::now(),
+                });
+            }
+
+            let total = table.rows.len();
+            let mut produced = 0;
+            for (&partition, records) in &by_partition {
+                let partition_client = client
+                    .partition_client(&topic, partition, UnknownTopicHandling::Error)
+                    .await
+                    .map_err(|e| KafkaError::SendFailed(e.to_string()))?;
+                for chunk in records.chunks(CSV_CHUNK_SIZE) {
+                    produced += partition_client
+                        .produce(chunk.to_vec(), compression)
+                        .await
+                        .map_err(|e| KafkaError::SendFailed(e.to_string()))?
+                        .len();
+                    on_progress(CsvProgress { produced, total });
+                }
+            }
+
+            Ok(CsvProduceResult {
+                produced,
+                partitions: by_partition.into_keys().collect(),
+            })
+        };
+
+        // As with imports, cancelling stops before the next chunk
+        let produce_future = self.operations.run("CSV produce", produce_future);
+        match tokio::time::timeout(std::time::Duration::from_secs(60), produce_future).await {
+            Ok(result) => result,
+            Err(_) => Err(KafkaError::ConnectionTimeout(60)),
+        }
+    }
+
+    /// Consume up to `max_messages` from each partition of `topic` concurrently and merge them
+    /// in `options.order_by` order.
+    ///
+    /// Kafka only orders records within a partition, so the timestamp merge is approximate:
+    /// producer clocks differ and CreateTime can go backwards within a partition, in which case
+    /// the merge can put a record before one with a lower offset on the same partition. Use
+    /// `OrderBy::PartitionThenOffset` when per-partition offset order matters.
+    ///
+    /// Each partition starts at its entry in `offsets`, falling back to `offset`; the per-partition
+    /// range handling of `options.offset_reset` applies to each. Empty partitions contribute
+    /// nothing. Records that fail to decode under `decode_policy` are left out.
+    pub async fn consume_all_partitions(
+        &self,
+        topic: String,
+        offset: i64,
+        offsets: Option<HashMap<i32, i64>>,
+        max_messages: i32,
+        decode_policy: DecodePolicy,
+        options: ConsumeOptions,
+    ) -> Result<MultiPartitionConsumeResult, KafkaError> {
+        let config = { self.config.lock().await.clone() };
+        let partitions = if config.in_memory {
+            self.memory.broker().await.partition_count(&topic)?
+        } else {
+            let topic = topic.as_str();
+            let count_future = self.with_reconnect(&config, 10, move |client| async move {
+                Self::partition_count(&client, topic).await
+            });
+            match tokio::time::timeout(std::time::Duration::from_secs(10), count_future).await {
+                Ok(result) => result?,
+                Err(_) => return Err(KafkaError::ConnectionTimeout(10)),
+            }
+        };
+        let offsets = offsets.unwrap_or_default();
+
+        let mut consumes = JoinSet::new();
+        for partition in 0..partitions {
+            let service = self.clone_service();
+            let topic = topic.clone();
+            let start = offsets.get(&partition).copied().unwrap_or(offset);
+            let options = options.clone();
+            consumes.spawn(async move {
+                let fetched = service
+                    .consume_records(topic, partition, start, max_messages, false, &options)
+                    .await?;
+                Ok::<_, KafkaError>((partition, fetched))
+            });
+        }
+
+        let mut result = MultiPartitionConsumeResult {
+            messages: Vec::new(),
+            next_offsets: HashMap::new(),
+            truncated: false,
+        };
+        while let Some(joined) = consumes.join_next().await {
+            let (partition, fetched) =
+                joined.map_err(|e| KafkaError::ConsumeFailed(e.to_string()))??;
+            result.truncated |= fetched.truncated;
+            result.next_offsets.insert(partition, fetched.next_offset);
+            result.messages.extend(
+                decode_records(
+                    fetched.records,
+                    partition,
+                    decode_policy,
+                    options.value_truncate_bytes,
+                )
+                .messages,
+            );
+        }
+        match options.order_by {
+            OrderBy::Timestamp => result
+                .messages
+                .sort_by_key(|message| (message.timestamp, message.partition, message.offset)),
+            OrderBy::PartitionThenOffset => result
+                .messages
+                .sort_by_key(|message| (message.partition, message.offset)),
+        }
+
+        Ok(result)
+    }
+
+    /// Fetch raw records from one partition for the consume commands, as an operation
+    /// `cancel_all_operations` can stop
+    async fn consume_records(
+        &self,
+        topic: String,
+        partition: i32,
+        offset: i64,
+        max_messages: i32,
+        dedup_by_key: bool,
+        options: &ConsumeOptions,
+    ) -> Result<FetchedRecords, KafkaError> {
+        let fetch = self.fetch_partition_records(
+            topic,
+            partition,
+            offset,
+            max_messages,
+            dedup_by_key,
+            options,
+        );
+        self.operations.run("Consume", fetch).await
+    }
+
+    async fn fetch_partition_records(
+        &self,
+        topic: String,
+        partition: i32,
+        offset: i64,
+        max_messages: i32,
+        dedup_by_key: bool,
+        options: &ConsumeOptions,
+    ) -> Result<FetchedRecords, KafkaError> {
+        const DEFAULT_CONSUME_DEADLINE_MS: u64 = 15_000;
+        const MAX_FETCH_WAIT: std::time::Duration = std::time::Duration::from_secs(5);
+
+        let config = { self.config.lock().await.clone() };
+        let budget = std::time::Duration::from_millis(
+            options.deadline_ms.unwrap_or(DEFAULT_CONSUME_DEADLINE_MS),
+        );
+        let started = Instant::now();
+        let remaining = || budget.saturating_sub(started.elapsed());
+        let max_messages = max_messages.max(0) as usize;
+        let from_timestamp = options
+            .from_timestamp_ms
+            .map(|timestamp_ms| {
+                Utc.timestamp_millis_opt(timestamp_ms)
+                    .single()
+                    .ok_or_else(|| {
+                        KafkaError::InvalidConfig(format!("Invalid timestamp {}", timestamp_ms))
+                    })
+            })
+            .transpose()?;
+
+        if config.in_memory {
+            return self
+                .memory
+                .fetch_records(
+                    &topic,
+                    partition,
+                    offset,
+                    max_messages,
+                    dedup_by_key,
+                    options,
+                )
+                .await;
+        }
+        if options.isolation_level == IsolationLevel::ReadUncommitted {
+            return Err(KafkaError::Unsupported(
+                "Consuming with read_uncommitted isolation is not supported: the Kafka client \
+                 library always fetches read_committed"
+                    .to_string(),
+            ));
+        }
+
+        let setup_future = async {
+            let topic = topic.as_str();
+            self.with_reconnect(&config, 15, move |client| async move {
+                let partition_client = client
+                    .partition_client(topic, partition, UnknownTopicHandling::Error)
+                    .await
+                    .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?;
+                let range = Self::offset_range(&partition_client).await?;
+                // The broker answers -1 when no record is at or after the timestamp
+                let timestamp_offset = match from_timestamp {
+                    Some(timestamp) => Some(
+                        partition_client
+                            .get_offset(OffsetAt::Timestamp(timestamp))
+                            .await
+                            .map_err(|e| {
+                                KafkaError::ConsumeFailed(format!(
+                                    "Failed to look up offset: {}",
+                                    e
+                                ))
+                            })?,
+                    ),
+                    None => None,
+                };
+                Ok((partition_client, range, timestamp_offset))
+            })
+            .await
+        };
+        let (partition_client, (earliest, latest), timestamp_offset) =
+            match tokio::time::timeout(remaining(), setup_future).await {
+                Ok(result) => result?,
+                Err(_) => {
+                    return Err(KafkaError::ConnectionTimeout(
+                        budget.as_secs_f64().ceil() as u64
+                    ))
+                }
+            };
+        let offset = match timestamp_offset {
+            Some(offset) if offset < 0 => {
+                return Ok(FetchedRecords {
+                    records: vec![],
+                    truncated: false,
+                    next_offset: latest,
+                })
+            }
+            Some(offset) => offset,
+            None => offset,
+        };
+
+        let mut records = Vec::new();
+        let mut truncated = false;
+        let mut total_bytes = 0;
+        let mut byte_cap_reached = false;
+        let mut next_offset = options.offset_reset.resolve(offset, earliest, latest)?;
+        while next_offset < latest && records.len() < max_messages && !byte_cap_reached {
+            let remaining = remaining();
+            if remaining.is_zero() {
+                truncated = true;
+                break;
+            }
+
+            // The last round only waits as long as the budget allows
+            let max_wait_ms = remaining.min(MAX_FETCH_WAIT).as_millis() as i32;
+            let fetch = partition_client.fetch_records(next_offset, 1..1_048_576, max_wait_ms);
+            let page = match tokio::time::timeout(remaining, fetch).await {
+                Ok(result) => {
+                    result
+                        .map_err(|e| KafkaError::ConsumeFailed(e.to_string()))?
+                        .0
+                }
+                Err(_) => {
+                    truncated = true;
+                    break;
+                }
+            };
+
+            let page_start = next_offset;
+            for record in page {
+                // Fetches return whole batches, which may start before the requested offset
+                if record.offset < next_offset {
+                    continue;
+                }
+                if record.offset >= latest || records.len() >= max_messages {
+                    break;
+                }
+                if !options.key_matches(record.record.key.as_deref()) {
+                    next_offset = record.offset + 1;
+                    continue;
+                }
+                let size = record.record.value.as_ref().map_or(0, Vec::len);
+                if options
+                    .max_total_bytes
+                    .is_some_and(|cap| total_bytes + size > cap)
+                {
+                    byte_cap_reached = true;
+                    break;
+                }
+                total_bytes += size;
+                next_offset = record.offset + 1;
+                records.push(record);
+            }
+            if next_offset == page_start {
+                break;
+            }
+        }
+
+        let records = if dedup_by_key {
+            dedup_records_by_key(records, options.include_tombstones)
+        } else {
+            records
+        };
+
+        Ok(FetchedRecords {
+            records,
+            truncated,
+            next_offset,
+        })
+    }
+}
+
+/// Explain a `SendTimeout` so the user can decide whether resending risks a duplicate
+fn describe_send_timeout(produce_attempted: bool) -> &'static str {
+    if produce_attempted {
+        "awaiting the broker ack; the message may have been delivered"
+    } else {
+        "before the message was produced; it was not delivered"
+    }
+}
+
+/// Error for a produce session id that isn't open
+fn unknown_session(session_id: &str) -> KafkaError {
+    KafkaError::InvalidConfig(format!(
+        "Unknown or expired produce session '{}'",
+        session_id
+    ))
+}
+
+/// Drop sessions idle for longer than `PRODUCE_SESSION_IDLE_TIMEOUT`, releasing their partition
+/// clients
+fn close_idle_sessions(sessions: &mut HashMap<String, ProduceSession>) {
+    sessions.retain(|_, session| session.last_used.elapsed() < PRODUCE_SESSION_IDLE_TIMEOUT);
+}
+
+/// Where `consume_new_since_last` resumes a partition spanning `earliest..latest`: the stored
+/// offset, or `latest` when there is none. A stored offset below `earliest` (records removed by
+/// retention) resumes at `earliest`; one past `latest` (the topic was deleted and recreated)
+/// resets to `latest` as a new baseline.
+fn resume_offset(stored: Option<i64>, earliest: i64, latest: i64) -> i64 {
+    match stored {
+        Some(offset) if offset > latest => latest,
+        Some(offset) => offset.max(earliest),
+        None => latest,
+    }
+}
+
+/// Error for operations (transactions) that need APIs the Kafka client doesn't implement
+fn unsupported_api(operation: &str, api: &str) -> KafkaError {
+    KafkaError::Unsupported(format!(
+        "{} requires the {} API, which the Kafka client library does not implement",
+        operation, api
+    ))
+}
+
+/// Build the record `send_message` produces, with its idempotency key when `options.idempotent`.
+///
+/// The key is encoded per `options.key_encoding` (or the UTF-8 `default_key` is used), the
+/// configured `default_headers` are added under `options.headers`, then the stamped `client-id`
+/// header (see `stamp_client_id_header`) and the `idempotency-key` header win over both. With
+/// `validate_json` the decoded value must parse as JSON.
+pub fn build_send_record(
+    config: &AppConfig,
+    message: String,
+    key: Option<String>,
+    options: SendOptions,
+) -> Result<(Record, Option<String>), KafkaError> {
+    let key = match key {
+        Some(key) => Some(options.key_encoding.encode(key, "key")?),
+        None => config.default_key.clone().map(String::into_bytes),
+    };
+    let timestamp = match options.timestamp_ms {
+        Some(timestamp_ms) => Utc
+            .timestamp_millis_opt(timestamp_ms)
+            .single()
+            .ok_or_else(|| {
+                KafkaError::InvalidInput(format!("Invalid timestamp_ms {}", timestamp_ms))
+            })?,
+        None => Utc::now(),
+    };
+    let idempotency_key = if options.idempotent {
+        Some(
+            options
+                .idempotency_key
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
+        )
+    } else {
+        None
+    };
+
+    let mut headers: BTreeMap<String, Vec<u8>> = config
+        .default_headers
+        .iter()
+        .map(|(name, value)| (name.clone(), value.clone().into_bytes()))
+        .collect();
+    headers.extend(
+        options
+            .headers
+            .into_iter()
+            .map(|(name, value)| (name, value.into_bytes())),
+    );
+    headers.extend(config.stamped_headers());
+    if let Some(idempotency_key) = &idempotency_key {
+        headers.insert(
+            IDEMPOTENCY_KEY_HEADER.to_string(),
+            idempotency_key.clone().into_bytes(),
+        );
+    }
+
+    let payload = options.value_encoding.encode(message, "Message payload")?;
+    if options.validate_json.unwrap_or(config.validate_json) {
+        serde_json::from_slice::<serde::de::IgnoredAny>(&payload)
+            .map_err(|e| KafkaError::InvalidConfig(format!("invalid JSON: {}", e)))?;
+    }
+    let value = match options.confluent_schema_id {
+        Some(schema_id) => confluent_frame(schema_id, &payload),
+        None => payload,
+    };
+
+    let record = Record {
+        key,
+        value: Some(value),
+        headers,
+        timestamp,
+    };
+    Ok((record, idempotency_key))
+}
+
+/// Prefix `payload` with the Confluent wire format header: magic byte 0 and the big-endian schema
+/// id
+fn confluent_frame(schema_id: u32, payload: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(5 + payload.len());
+    framed.push(0);
+    framed.extend_from_slice(&schema_id.to_be_bytes());
+    framed.extend_from_slice(payload);
+    framed
+}
+
+/// The current time as (seconds, milliseconds, RFC 3339) for `SendResult`, all from one reading
+fn send_timestamps() -> (u64, i64, String) {
+    let now = SystemTime::now();
+    let since_epoch = now.duration_since(SystemTime::UNIX_EPOCH).unwrap();
+    (
+        since_epoch.as_secs(),
+        since_epoch.as_millis() as i64,
+        DateTime::<Utc>::from(now).to_rfc3339_opts(SecondsFormat::Millis, true),
+    )
+}
+
+/// Decode records read from `partition` under `policy`, separating out the ones that fail.
+/// Values are truncated to `value_truncate_bytes` after `total_bytes` is counted.
+pub fn decode_records(
+    records: Vec<RecordAndOffset>,
+    partition: i32,
+    policy: DecodePolicy,
+    value_truncate_bytes: Option<usize>,
+) -> DetailedConsumeResult {
+    let mut result = DetailedConsumeResult {
+        messages: Vec::new(),
+        decode_errors: Vec::new(),
+        truncated: false,
+        total_bytes: 0,
+    };
+    for record in records {
+        let offset = record.offset;
+        match ConsumedMessage::decode(record, partition, policy) {
+            Ok(mut message) => {
+                // Raw bytes, the unit `max_total_bytes` caps, not the decoded (e.g. base64) length
+                result.total_bytes += message.value_full_length;
+                if let Some(max_bytes) = value_truncate_bytes {
+                    message.truncate_value(max_bytes);
+                }
+                result.messages.push(message);
+            }
+            Err(error) => result.decode_errors.push(DecodeError { offset, error }),
+        }
+    }
+    result
+}
+
+/// Whether the broker rejected a request for lack of topic or cluster permissions
+fn is_authorization_error(error: &rskafka::client::error::Error) -> bool {
+    matches!(
+        error,
+        rskafka::client::error::Error::ServerError {
+            protocol_error: ProtocolError::TopicAuthorizationFailed
+                | ProtocolError::ClusterAuthorizationFailed,
+            ..
+        }
+    )
+}
+
+/// Whether a send failed because the broker couldn't be reached at all, so nothing was written
+fn is_unreachable_error(error: &KafkaError) -> bool {
+    matches!(
+        error,
+        KafkaError::ConnectionFailed(_)
+            | KafkaError::ConnectionTimeout(_)
+            | KafkaError::SendTimeout {
+                produce_attempted: false,
+                ..
+            }
+    )
+}
+
+/// Whether an error means the broker connection was dropped (rather than the request failing)
+fn is_disconnect_error(error: &KafkaError) -> bool {
+    const DISCONNECT_MARKERS: [&str; 6] = [
+        "Connection is poisoned",
+        "Cannot read framed message",
+        "Cannot read/write data",
+        "broken pipe",
+        "connection reset",
+        "connection closed",
+    ];
+
+    let message = match error {
+        KafkaError::ConnectionFailed(message)
+        | KafkaError::SendFailed(message)
+        | KafkaError::ConsumeFailed(message)
+        | KafkaError::MetadataFailed(message) => message.to_lowercase(),
+        _ => return false,
+    };
+    DISCONNECT_MARKERS
+        .iter()
+        .any(|marker| message.contains(&marker.to_lowercase()))
+}
+
+/// Nearest-rank percentile (`p` in 0..=1) of an ascending, non-empty slice
+fn nearest_rank<T: Copy>(sorted: &[T], p: f64) -> T {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Whether `list_topics` lists `name`: internal (`_`-prefixed) topics only with
+/// `include_internal`, and only names matching a non-empty `name_filter`
+pub fn topic_visible(name: &str, name_filter: Option<&str>, include_internal: bool) -> bool {
+    if !include_internal && name.starts_with('_') {
+        return false;
+    }
+    match name_filter {
+        Some(filter) if !filter.is_empty() => topic_name_matches(name, filter),
+        _ => true,
+    }
+}
+
+/// Match a topic name against a glob (`*`, `?`) or, without wildcards, a prefix
+pub fn topic_name_matches(name: &str, filter: &str) -> bool {
+    if !filter.contains(['*', '?']) {
+        return name.starts_with(filter);
+    }
+
+    let name: Vec<char> = name.chars().collect();
+    let pattern: Vec<char> = filter.chars().collect();
+    let (mut n, mut p) = (0, 0);
+    // Position of the last `*` seen in the pattern and the name index it was matched at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some('?') => {
+                n += 1;
+                p += 1;
+            }
+            Some(&c) if c == name[n] => {
+                n += 1;
+                p += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` swallow one more character and retry
+                Some((star_p, star_n)) => {
+                    backtrack = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Resolve the compression for a produce: a per-call override wins over the configured default
+pub fn resolve_compression(
+    default: CompressionCodec,
+    override_codec: Option<CompressionCodec>,
+) -> Compression {
+    match override_codec.unwrap_or(default) {
+        CompressionCodec::NoCompression => Compression::NoCompression,
+        CompressionCodec::Gzip => Compression::Gzip,
+        CompressionCodec::Snappy => Compression::Snappy,
+        CompressionCodec::Lz4 => Compression::Lz4,
+        CompressionCodec::Zstd => Compression::Zstd,
+    }
+}
+
+/// Keep only the highest-offset record for each distinct key, ordered by timestamp.
+///
+/// Records without a key are treated as unique and always kept. A key whose highest-offset record
+/// is a tombstone (null value) has been deleted, so it is dropped entirely unless
+/// `include_tombstones` keeps the tombstone itself.
+pub fn dedup_records_by_key(
+    records: Vec<RecordAndOffset>,
+    include_tombstones: bool,
+) -> Vec<RecordAndOffset> {
+    let mut latest: HashMap<Vec<u8>, i64> = HashMap::new();
+    for record in &records {
+        if let Some(key) = &record.record.key {
+            let offset = latest.entry(key.clone()).or_insert(record.offset);
+            *offset = (*offset).max(record.offset);
+        }
+    }
+
+    let mut survivors: Vec<RecordAndOffset> = records
+        .into_iter()
+        .filter(|record| match &record.record.key {
+            Some(key) => {
+                latest.get(key) == Some(&record.offset)
+                    && (include_tombstones || record.record.value.is_some())
+            }
+            None => true,
+        })
+        .collect();
+    survivors.sort_by_key(|record| record.record.timestamp);
+    survivors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn send_result_reports_configured_default_compression() {
+        let result = SendResult::sent(
+            "sent",
+            resolve_compression(CompressionCodec::Gzip, None),
+            None,
+        );
+        assert_eq!(result.compression, "Gzip");
+    }
+
+    #[test]
+    fn send_result_reports_compression_override() {
+        let compression = resolve_compression(
+            CompressionCodec::Gzip,
+            Some(CompressionCodec::NoCompression),
+        );
+        let result = SendResult::sent("sent", compression, None);
+        assert_eq!(result.compression, "NoCompression");
+    }
+
+    #[test]
+    fn topic_filter_without_wildcards_is_a_prefix() {
+        assert!(topic_name_matches("orders", "ord"));
+        assert!(topic_name_matches("orders", "orders"));
+        assert!(topic_name_matches("orders", ""));
+        assert!(!topic_name_matches("orders", "orders-v2"));
+        assert!(!topic_name_matches("old-orders", "orders"));
+    }
+
+    #[test]
+    fn topic_filter_with_wildcards_is_a_glob() {
+        assert!(topic_name_matches("orders.eu.v1", "orders.*"));
+        assert!(topic_name_matches("orders.eu.v1", "*.eu.*"));
+        assert!(topic_name_matches("orders.eu.v1", "orders.??.v1"));
+        assert!(topic_name_matches("orders", "*"));
+        assert!(!topic_name_matches("orders.eu.v1", "orders.?.v1"));
+        // A glob must match the whole name, unlike a prefix
+        assert!(!topic_name_matches("orders.eu.v1", "orders.e?"));
+        assert!(!topic_name_matches("orders.eu", "orders.eu*v1"));
+    }
+
+    #[test]
+    fn internal_topics_are_excluded_unless_requested() {
+        assert!(!topic_visible("__consumer_offsets", None, false));
+        assert!(!topic_visible("_schemas", None, false));
+        assert!(topic_visible("__consumer_offsets", None, true));
+        assert!(topic_visible("orders", None, false));
+    }
+
+    #[test]
+    fn internal_topics_still_honour_the_filter() {
+        assert!(topic_visible(
+            "__consumer_offsets",
+            Some("__consumer*"),
+            true
+        ));
+        assert!(!topic_visible(
+            "__consumer_offsets",
+            Some("__consumer*"),
+            false
+        ));
+        assert!(!topic_visible("__consumer_offsets", Some("orders"), true));
+        assert!(topic_visible("orders", Some(""), false));
+    }
+
+    #[test]
+    fn total_bytes_counts_raw_value_bytes() {
+        let record = RecordAndOffset {
+            record: Record {
+                key: None,
+                value: Some(vec![0xff, 0xfe, 0xfd]),
+                headers: BTreeMap::new(),
+                timestamp: Utc::now(),
+            },
+            offset: 0,
+        };
+        let result = decode_records(vec![record], 0, DecodePolicy::Base64Fallback, None);
+        assert_eq!(result.messages[0].value_encoding, ByteEncoding::Base64);
+        assert_eq!(result.total_bytes, 3);
+    }
+
+    #[test]
+    fn resume_offset_clamps_to_the_partition_range() {
+        assert_eq!(resume_offset(None, 5, 20), 20);
+        assert_eq!(resume_offset(Some(12), 5, 20), 12);
+        assert_eq!(resume_offset(Some(20), 5, 20), 20);
+        assert_eq!(resume_offset(Some(2), 5, 20), 5);
+        assert_eq!(resume_offset(Some(40), 5, 20), 20);
+    }
+
+    fn test_service() -> KafkaService {
+        KafkaService::new(
+            AppConfig::default(),
+            PositionStore::default(),
+            OfflineQueue::default(),
+        )
+    }
+
+    fn in_memory_service() -> KafkaService {
+        let config = AppConfig {
+            in_memory: true,
+            ..AppConfig::default()
+        };
+        KafkaService::new(config, PositionStore::default(), OfflineQueue::default())
+    }
+
+    #[tokio::test]
+    async fn in_memory_mode_serves_reads_without_a_broker() {
+        let service = in_memory_service();
+        let topic = service.get_config().await.topic;
+        let sent = service
+            .send_message("hello".to_string(), None, SendOptions::default())
+            .await
+            .unwrap();
+        let offset = sent.offset.unwrap();
+
+        let message = service
+            .get_message_at(topic.clone(), 0, offset)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.value.as_deref(), Some("hello"));
+        assert!(service
+            .get_message_at(topic.clone(), 0, offset + 1)
+            .await
+            .unwrap()
+            .is_none());
+
+        let offsets = service.offsets_for_times(topic.clone(), 0).await.unwrap();
+        assert_eq!(offsets[0].offset, 0);
+        assert!(!offsets[0].latest_fallback);
+        assert_eq!(
+            service
+                .sample_topic_stats(topic, 0, 10)
+                .await
+                .unwrap()
+                .count,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn in_memory_mode_reports_broker_only_operations_as_unsupported() {
+        let service = in_memory_service();
+        let topic = service.get_config().await.topic;
+        let result = service.estimate_consume(topic).await;
+        assert!(matches!(result, Err(KafkaError::Unsupported(_))));
+    }
+
+    #[tokio::test]
+    async fn repeat_appends_the_sequence_to_utf8_values() {
+        let service = in_memory_service();
+        let topic = service.get_config().await.topic;
+        let options = SendOptions {
+            repeat: Some(3),
+            append_sequence: true,
+            ..SendOptions::default()
+        };
+        let sent = service
+            .send_message("copy".to_string(), None, options)
+            .await
+            .unwrap();
+        assert_eq!(sent.repeat.unwrap().succeeded, 3);
+
+        let last = service.get_message_at(topic, 0, 2).await.unwrap().unwrap();
+        assert_eq!(last.value.as_deref(), Some("copy-2"));
+    }
+
+    #[tokio::test]
+    async fn repeat_rejects_a_shared_idempotency_key_and_encoded_sequences() {
+        let service = in_memory_service();
+        let keyed = SendOptions {
+            repeat: Some(2),
+            idempotency_key: Some("order-1".to_string()),
+            ..SendOptions::default()
+        };
+        let result = service.send_message("copy".to_string(), None, keyed).await;
+        assert!(matches!(result, Err(KafkaError::InvalidInput(_))));
+
+        let hex = SendOptions {
+            repeat: Some(2),
+            append_sequence: true,
+            value_encoding: Encoding::Hex,
+            ..SendOptions::default()
+        };
+        let result = service.send_message("00ff".to_string(), None, hex).await;
+        assert!(matches!(result, Err(KafkaError::InvalidInput(_))));
+    }
+
+    async fn assert_no_session_state(service: &KafkaService) {
+        assert!(service.produce_sessions.lock().await.is_empty());
+        assert!(service.cached_client.lock().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn transactions_report_unsupported_without_leaving_state() {
+        let service = test_service();
+
+        let begin = service.begin_transaction("txn-1".to_string()).await;
+        assert!(
+            matches!(begin, Err(KafkaError::Unsupported(ref message)) if message.contains("txn-1"))
+        );
+        assert_no_session_state(&service).await;
+
+        // Nothing was opened, so commit and abort fail the same way rather than finding one
+        assert!(matches!(
+            service.commit_transaction().await,
+            Err(KafkaError::Unsupported(_))
+        ));
+        assert_no_session_state(&service).await;
+        assert!(matches!(
+            service.abort_transaction().await,
+            Err(KafkaError::Unsupported(_))
+        ));
+        assert_no_session_state(&service).await;
+    }
 }
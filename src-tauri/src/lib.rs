@@ -1,16 +1,66 @@
+mod app_info;
+mod backend;
+mod batcher;
+mod circuit;
 mod config;
+mod csv;
+mod export;
+mod format;
 mod kafka;
+mod logs;
+mod memory;
+mod operations;
+mod partitioner;
+mod positions;
+mod secrets;
+mod spool;
+mod template;
 
-use config::{AppConfig, ConfigError};
-use kafka::{KafkaError, KafkaService, SendResult, TopicCreateResult, ConsumedMessage};
+use app_info::AppInfo;
+use backend::KafkaBackend;
+use config::{AppConfig, ConfigError, SampleConfig, SecurityProtocol, TopicSettings};
+use csv::CsvValueFormat;
+use export::ExportOptions;
+use format::TopicFormatReport;
+use kafka::{
+    BatchSendResult, ClusterMetadata, ConsumeEstimate, ConsumeOptions, ConsumedMessage,
+    CredentialRotationResult, CsvProduceResult, DecodePolicy, DetailedConsumeResult, Encoding,
+    ExportResult, ImportResult, KafkaError, KafkaService, KeyHistory, LatencyReport,
+    MultiPartitionConsumeResult, OfflineFlushResult, OrderVerification, OrderedProduceResult,
+    PartialResult, PartitionHealth, PartitionOffset, PingResult, ProduceSessionSummary, RecordSpec,
+    ReliabilityReport, SendOptions, SendResult, TopicComparison, TopicCreateResult,
+    TopicSendResult, TopicStats, UnsupportedOperation, WaitForMessagesResult,
+};
+use logs::{LogForwarder, LogLevel, LogStream};
+use positions::PositionStore;
 use serde::Serialize;
+use spool::OfflineQueue;
+use std::collections::HashMap;
 use std::sync::Arc;
-use tauri::State;
-use tokio::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State, WindowEvent};
+use tokio::sync::{watch, Mutex};
 
 /// Application state holding the Kafka service
 pub struct AppState {
     kafka_service: Arc<Mutex<KafkaService>>,
+    log_stream: Arc<LogStream>,
+    /// Stop signal of the running `start_tailing` task; a `watch` channel like the cancellation in
+    /// `OperationRegistry`, since tokio-util's `CancellationToken` isn't a dependency
+    tail: std::sync::Mutex<Option<watch::Sender<bool>>>,
+}
+
+impl AppState {
+    /// Signal the running tail, if any, to stop, returning whether there was one
+    fn stop_tailing(&self) -> bool {
+        match self.tail.lock().unwrap().take() {
+            Some(stop) => {
+                // The task may have already ended on an error; that's fine
+                let _ = stop.send(true);
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 /// Combined result type for Tauri commands
@@ -39,14 +89,141 @@ impl<T> From<Result<T, ConfigError>> for CommandResult<T> {
     }
 }
 
-/// Send a message to Kafka
+/// Send a message to Kafka, optionally keyed for partition routing or to a chosen partition.
+/// `headers` are added to `options.headers`, replacing any of the same name; `encoding` says how
+/// `message` becomes the value bytes (UTF-8 unless set). `repeat` (or `options.repeat`) sends it
+/// several times.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn send_kafka_message(
     state: State<'_, AppState>,
     message: String,
+    key: Option<String>,
+    partition: Option<i32>,
+    headers: Option<HashMap<String, String>>,
+    encoding: Option<Encoding>,
+    repeat: Option<u32>,
+    options: Option<SendOptions>,
+) -> Result<CommandResult<SendResult>, ()> {
+    let mut options = options.unwrap_or_default();
+    if partition.is_some() {
+        options.partition = partition;
+    }
+    if repeat.is_some() {
+        options.repeat = repeat;
+    }
+    if let Some(encoding) = encoding {
+        options.value_encoding = encoding;
+    }
+    options.headers.extend(headers.unwrap_or_default());
+    let spec = RecordSpec {
+        value: message,
+        key,
+        options,
+    };
+    send_record(state, spec).await
+}
+
+/// Send one record described by a full spec (value, key, headers, partition, ...)
+#[tauri::command]
+async fn send_record(
+    state: State<'_, AppState>,
+    spec: RecordSpec,
 ) -> Result<CommandResult<SendResult>, ()> {
     let service = state.kafka_service.lock().await.clone_service();
-    Ok(service.send_message(message).await.into())
+    Ok(
+        KafkaBackend::send_message(&service, spec.value, spec.key, spec.options)
+            .await
+            .into(),
+    )
+}
+
+/// Number of messages waiting in the offline queue
+#[tauri::command]
+async fn offline_queue_depth(state: State<'_, AppState>) -> Result<usize, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.offline_queue_depth().await)
+}
+
+/// Replay messages spooled while the broker was unreachable
+#[tauri::command]
+async fn flush_offline_queue(
+    state: State<'_, AppState>,
+) -> Result<CommandResult<OfflineFlushResult>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.flush_offline_queue().await.into())
+}
+
+/// Send several messages to Kafka over one connection
+#[tauri::command]
+async fn send_kafka_messages_batch(
+    state: State<'_, AppState>,
+    messages: Vec<String>,
+    max_in_flight: Option<usize>,
+    linger_ms: Option<u64>,
+    batch_size: Option<usize>,
+    timeout_secs: Option<u64>,
+) -> Result<CommandResult<BatchSendResult>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service
+        .send_messages_batch(messages, max_in_flight, linger_ms, batch_size, timeout_secs)
+        .await
+        .into())
+}
+
+/// Send `count` messages generated from a template with `{{uuid}}`, `{{timestamp}}`, `{{seq}}`
+/// and `{{random_int(min,max)}}` placeholders
+#[tauri::command]
+async fn send_templated_message(
+    state: State<'_, AppState>,
+    template: String,
+    count: Option<u32>,
+) -> Result<CommandResult<BatchSendResult>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let count = count.unwrap_or(1);
+    Ok(service.send_templated_message(template, count).await.into())
+}
+
+/// Send the same message to several topics
+#[tauri::command]
+async fn send_to_topics(
+    state: State<'_, AppState>,
+    topics: Vec<String>,
+    message: String,
+) -> Result<CommandResult<Vec<TopicSendResult>>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.send_to_topics(topics, message).await.into())
+}
+
+/// Open a session for producing messages in chunks
+#[tauri::command]
+async fn open_produce_session(
+    state: State<'_, AppState>,
+    topic: Option<String>,
+) -> Result<CommandResult<String>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.open_produce_session(topic).await.into())
+}
+
+/// Produce a chunk of messages in an open session
+#[tauri::command]
+async fn produce_chunk(
+    state: State<'_, AppState>,
+    session_id: String,
+    lines: Vec<String>,
+) -> Result<CommandResult<ProduceSessionSummary>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.produce_chunk(session_id, lines).await.into())
+}
+
+/// Close a produce session and return its totals
+#[tauri::command]
+async fn finish_produce(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<CommandResult<ProduceSessionSummary>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.finish_produce(session_id).await.into())
 }
 
 /// Get the current Kafka configuration
@@ -56,6 +233,24 @@ async fn get_kafka_config(state: State<'_, AppState>) -> Result<AppConfig, ()> {
     Ok(service.get_config().await)
 }
 
+/// Report the app version and compiled-in features
+#[tauri::command]
+async fn app_info() -> Result<AppInfo, ()> {
+    Ok(AppInfo::current())
+}
+
+/// Operations the Kafka client library can't support, with the protocol APIs they'd need
+#[tauri::command]
+async fn unsupported_operations() -> Result<Vec<UnsupportedOperation>, ()> {
+    Ok(UnsupportedOperation::all())
+}
+
+/// Get a config template for a security protocol and the fields it requires
+#[tauri::command]
+async fn sample_config(protocol: SecurityProtocol) -> Result<SampleConfig, ()> {
+    Ok(AppConfig::sample_config(protocol))
+}
+
 /// Save Kafka configuration
 #[tauri::command]
 async fn save_kafka_config(
@@ -65,11 +260,74 @@ async fn save_kafka_config(
     // Update runtime config
     let service = state.kafka_service.lock().await.clone_service();
     service.update_config(config.clone()).await;
-    
+
     // Persist to disk
     Ok(config.save().into())
 }
 
+/// Get the per-topic setting overrides
+#[tauri::command]
+async fn get_topic_overrides(
+    state: State<'_, AppState>,
+) -> Result<HashMap<String, TopicSettings>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.get_config().await.topic_overrides)
+}
+
+/// Set the overrides for a topic, or remove them when `settings` is omitted
+#[tauri::command]
+async fn set_topic_override(
+    state: State<'_, AppState>,
+    topic: String,
+    settings: Option<TopicSettings>,
+) -> Result<CommandResult<()>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.set_topic_override(topic, settings).await.into())
+}
+
+/// Move where config, consume positions and the offline queue are stored and reload them from
+/// `path`, returning the loaded config
+#[tauri::command]
+async fn set_config_dir(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<CommandResult<AppConfig>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.set_config_dir(path.into()).await.into())
+}
+
+/// Delete the saved config and consume positions and reset to the defaults; does nothing unless
+/// `confirm` is set
+#[tauri::command]
+async fn reset_config(
+    state: State<'_, AppState>,
+    confirm: bool,
+) -> Result<CommandResult<AppConfig>, ()> {
+    if !confirm {
+        return Ok(CommandResult::Err(
+            "Resetting the config requires confirm: true".to_string(),
+        ));
+    }
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.reset_config().await.into())
+}
+
+/// Replace the SASL credentials, persist them and test the connection
+#[tauri::command]
+async fn rotate_credentials(
+    state: State<'_, AppState>,
+    username: String,
+    password: String,
+    rollback_on_failure: Option<bool>,
+) -> Result<CommandResult<CredentialRotationResult>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let rollback = rollback_on_failure.unwrap_or(true);
+    Ok(service
+        .rotate_credentials(username, password, rollback)
+        .await
+        .into())
+}
+
 /// Test connection to Kafka broker with timeout
 #[tauri::command]
 async fn test_kafka_connection(
@@ -79,7 +337,34 @@ async fn test_kafka_connection(
     // Clone service ref and release state lock immediately to avoid blocking other commands
     let service = state.kafka_service.lock().await.clone_service();
     let timeout = timeout_secs.unwrap_or(10); // Default 10 second timeout
-    Ok(service.test_connection(timeout).await.into())
+    Ok(KafkaBackend::test_connection(&service, timeout)
+        .await
+        .into())
+}
+
+/// Time a metadata request over the live connection, for a latency readout
+#[tauri::command]
+async fn ping_broker(
+    state: State<'_, AppState>,
+    timeout_ms: Option<u64>,
+) -> Result<PingResult, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let timeout_ms = timeout_ms.unwrap_or(5000);
+    Ok(service.ping_broker(timeout_ms).await)
+}
+
+/// Cancel every long-running operation in flight, returning how many were cancelled
+#[tauri::command]
+async fn cancel_all_operations(state: State<'_, AppState>) -> Result<usize, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.cancel_all_operations())
+}
+
+/// Whether the connection circuit breaker is open (see the `kafka://circuit` event)
+#[tauri::command]
+async fn circuit_open(state: State<'_, AppState>) -> Result<bool, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.circuit_open().await)
 }
 
 /// Create a new Kafka topic
@@ -93,7 +378,276 @@ async fn create_kafka_topic(
     let service = state.kafka_service.lock().await.clone_service();
     let partitions = num_partitions.unwrap_or(1);
     let replication = replication_factor.unwrap_or(1);
-    Ok(service.create_topic(topic_name, partitions, replication).await.into())
+    Ok(
+        KafkaBackend::create_topic(&service, topic_name, partitions, replication)
+            .await
+            .into(),
+    )
+}
+
+/// Get the cluster shape (brokers, topics, partitions) in one call
+#[tauri::command]
+async fn get_cluster_metadata(
+    state: State<'_, AppState>,
+) -> Result<CommandResult<PartialResult<ClusterMetadata>>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.get_cluster_metadata().await.into())
+}
+
+/// Check a topic's partitions for under-replication
+#[tauri::command]
+async fn check_topic_health(
+    state: State<'_, AppState>,
+    topic: String,
+) -> Result<CommandResult<Vec<PartitionHealth>>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.check_topic_health(topic).await.into())
+}
+
+/// List topics, optionally filtered by a glob or prefix
+#[tauri::command]
+async fn list_kafka_topics(
+    state: State<'_, AppState>,
+    name_filter: Option<String>,
+    include_internal: Option<bool>,
+) -> Result<CommandResult<PartialResult<Vec<String>>>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let include_internal = include_internal.unwrap_or(false);
+    Ok(
+        KafkaBackend::list_topics(&service, name_filter, include_internal)
+            .await
+            .into(),
+    )
+}
+
+/// Estimate the size and duration of consuming a whole topic
+#[tauri::command]
+async fn estimate_consume(
+    state: State<'_, AppState>,
+    topic: String,
+) -> Result<CommandResult<ConsumeEstimate>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.estimate_consume(topic).await.into())
+}
+
+/// Compare two topics' per-partition message counts, e.g. to validate a mirror
+#[tauri::command]
+async fn compare_topics(
+    state: State<'_, AppState>,
+    topic_a: String,
+    topic_b: String,
+) -> Result<CommandResult<TopicComparison>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.compare_topics(topic_a, topic_b).await.into())
+}
+
+/// Guess the format of a topic's values from a sample of messages
+#[tauri::command]
+async fn detect_topic_format(
+    state: State<'_, AppState>,
+    topic: String,
+    sample: Option<i32>,
+) -> Result<CommandResult<TopicFormatReport>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let sample = sample.unwrap_or(20);
+    Ok(service.detect_topic_format(topic, sample).await.into())
+}
+
+/// Summarise message sizes and shape over a sample of recent messages on a partition (0 by
+/// default)
+#[tauri::command]
+async fn sample_topic_stats(
+    state: State<'_, AppState>,
+    topic: String,
+    sample_size: Option<i32>,
+    partition: Option<i32>,
+) -> Result<CommandResult<TopicStats>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let sample_size = sample_size.unwrap_or(100);
+    let partition = partition.unwrap_or(0);
+    Ok(service
+        .sample_topic_stats(topic, partition, sample_size)
+        .await
+        .into())
+}
+
+/// Measure produce→consume latency over a window, emitting `kafka://latency-sample` per probe
+#[tauri::command]
+async fn latency_probe(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    topic: String,
+    duration_secs: Option<u64>,
+    interval_ms: Option<u64>,
+) -> Result<CommandResult<LatencyReport>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let duration = duration_secs.unwrap_or(30);
+    let interval = interval_ms.unwrap_or(1000);
+    Ok(service
+        .latency_probe(topic, duration, interval, |sample| {
+            let _ = app.emit("kafka://latency-sample", sample);
+        })
+        .await
+        .into())
+}
+
+/// Wait until a topic holds at least `target_count` messages, emitting `kafka://wait-progress`
+/// on each poll
+#[tauri::command]
+async fn wait_for_messages(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    topic: String,
+    target_count: i64,
+    timeout_secs: Option<u64>,
+) -> Result<CommandResult<WaitForMessagesResult>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let timeout_secs = timeout_secs.unwrap_or(60);
+    Ok(service
+        .wait_for_messages(topic, target_count, timeout_secs, |progress| {
+            let _ = app.emit("kafka://wait-progress", progress);
+        })
+        .await
+        .into())
+}
+
+/// Follow a topic (the configured one by default) from its latest offset, emitting each new
+/// message as `kafka://message`, under the `kafka://` prefix all of the app's events use. A tail
+/// already running is stopped first. If the tail fails rather than being stopped, the error is
+/// emitted as `kafka://tail-error`.
+#[tauri::command]
+async fn start_tailing(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    topic: Option<String>,
+    decode_policy: Option<DecodePolicy>,
+) -> Result<CommandResult<()>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let topic = match topic {
+        Some(topic) => topic,
+        None => service.get_config().await.topic,
+    };
+    let policy = match decode_policy {
+        Some(policy) => policy,
+        None => service
+            .topic_settings(&topic)
+            .await
+            .decode_policy
+            .unwrap_or_default(),
+    };
+
+    let (stop, mut stopped) = watch::channel(false);
+    if let Some(previous) = state.tail.lock().unwrap().replace(stop) {
+        let _ = previous.send(true);
+    }
+    tauri::async_runtime::spawn(async move {
+        let message_app = app.clone();
+        let tail = service.tail_messages(topic, policy, move |message| {
+            let _ = message_app.emit("kafka://message", message);
+        });
+        // Stopping drops the tail future, which closes its fetches; a dropped sender stops too
+        tokio::select! {
+            result = tail => {
+                if let Err(e) = result {
+                    let _ = app.emit("kafka://tail-error", e.to_string());
+                }
+            }
+            _ = stopped.changed() => {}
+        }
+    });
+    Ok(CommandResult::Ok(()))
+}
+
+/// Stop the tail started by `start_tailing`, returning whether one was running
+#[tauri::command]
+async fn stop_tailing(state: State<'_, AppState>) -> Result<CommandResult<bool>, ()> {
+    Ok(CommandResult::Ok(state.stop_tailing()))
+}
+
+/// Consume messages, reporting records that fail to decode separately
+#[tauri::command]
+async fn consume_detailed(
+    state: State<'_, AppState>,
+    topic: String,
+    offset: Option<i64>,
+    max_messages: Option<i32>,
+    dedup_by_key: Option<bool>,
+    decode_policy: Option<DecodePolicy>,
+    options: Option<ConsumeOptions>,
+) -> Result<CommandResult<DetailedConsumeResult>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let start_offset = offset.unwrap_or(0);
+    let max = max_messages.unwrap_or(50);
+    let dedup = dedup_by_key.unwrap_or(false);
+    let policy = match decode_policy {
+        Some(policy) => policy,
+        None => service
+            .topic_settings(&topic)
+            .await
+            .decode_policy
+            .unwrap_or(DecodePolicy::Strict),
+    };
+    let options = options.unwrap_or_default();
+    Ok(service
+        .consume_detailed(topic, start_offset, max, dedup, policy, options)
+        .await
+        .into())
+}
+
+/// Consume every partition of a topic, merged by timestamp, optionally resuming each partition
+/// from `offsets`
+#[tauri::command]
+async fn consume_all_partitions(
+    state: State<'_, AppState>,
+    topic: String,
+    offset: Option<i64>,
+    offsets: Option<HashMap<i32, i64>>,
+    max_messages: Option<i32>,
+    decode_policy: Option<DecodePolicy>,
+    options: Option<ConsumeOptions>,
+) -> Result<CommandResult<MultiPartitionConsumeResult>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let start_offset = offset.unwrap_or(0);
+    let max = max_messages.unwrap_or(50);
+    let policy = match decode_policy {
+        Some(policy) => policy,
+        None => service
+            .topic_settings(&topic)
+            .await
+            .decode_policy
+            .unwrap_or_default(),
+    };
+    let options = options.unwrap_or_default();
+    Ok(service
+        .consume_all_partitions(topic, start_offset, offsets, max, policy, options)
+        .await
+        .into())
+}
+
+/// Fetch the single message at an exact offset
+#[tauri::command]
+async fn get_message_at(
+    state: State<'_, AppState>,
+    topic: String,
+    partition: Option<i32>,
+    offset: i64,
+) -> Result<CommandResult<Option<ConsumedMessage>>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let partition = partition.unwrap_or(0);
+    Ok(service
+        .get_message_at(topic, partition, offset)
+        .await
+        .into())
+}
+
+/// Consume only the messages on partition 0 that arrived since the last call for this topic
+#[tauri::command]
+async fn consume_new_since_last(
+    state: State<'_, AppState>,
+    topic: String,
+) -> Result<CommandResult<Vec<ConsumedMessage>>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.consume_new_since_last(topic).await.into())
 }
 
 /// Consume messages from a Kafka topic
@@ -103,33 +657,421 @@ async fn consume_kafka_messages(
     topic: String,
     offset: Option<i64>,
     max_messages: Option<i32>,
+    dedup_by_key: Option<bool>,
+    decode_policy: Option<DecodePolicy>,
+    options: Option<ConsumeOptions>,
 ) -> Result<CommandResult<Vec<ConsumedMessage>>, ()> {
     let service = state.kafka_service.lock().await.clone_service();
     let start_offset = offset.unwrap_or(0);
     let max = max_messages.unwrap_or(50);
-    Ok(service.consume_messages(topic, start_offset, max).await.into())
+    let dedup = dedup_by_key.unwrap_or(false);
+    let policy = match decode_policy {
+        Some(policy) => policy,
+        None => service
+            .topic_settings(&topic)
+            .await
+            .decode_policy
+            .unwrap_or_default(),
+    };
+    let options = options.unwrap_or_default();
+    Ok(
+        KafkaBackend::consume_messages(&service, topic, start_offset, max, dedup, policy, options)
+            .await
+            .into(),
+    )
+}
+
+/// Number of partitions of a topic, e.g. to offer a partition choice when consuming
+#[tauri::command]
+async fn get_partition_count(
+    state: State<'_, AppState>,
+    topic: String,
+) -> Result<CommandResult<i32>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.topic_partition_count(topic).await.into())
+}
+
+/// Map a timestamp to an offset on each partition of a topic
+#[tauri::command]
+async fn offsets_for_times(
+    state: State<'_, AppState>,
+    topic: String,
+    timestamp_ms: i64,
+) -> Result<CommandResult<Vec<PartitionOffset>>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.offsets_for_times(topic, timestamp_ms).await.into())
+}
+
+/// Produce records carrying a `seq` header to one partition for ordering tests
+#[tauri::command]
+async fn produce_ordered(
+    state: State<'_, AppState>,
+    topic: String,
+    count: usize,
+    partition: Option<i32>,
+) -> Result<CommandResult<OrderedProduceResult>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let partition = partition.unwrap_or(0);
+    Ok(service
+        .produce_ordered(topic, count, partition)
+        .await
+        .into())
+}
+
+/// Check that `seq` headers on a partition are strictly increasing
+#[tauri::command]
+async fn verify_ordered(
+    state: State<'_, AppState>,
+    topic: String,
+    partition: Option<i32>,
+    start_offset: Option<i64>,
+) -> Result<CommandResult<OrderVerification>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let partition = partition.unwrap_or(0);
+    let start_offset = start_offset.unwrap_or(0);
+    Ok(service
+        .verify_ordered(topic, partition, start_offset)
+        .await
+        .into())
+}
+
+/// The latest records with a key on one partition, newest first
+#[tauri::command]
+async fn key_history(
+    state: State<'_, AppState>,
+    topic: String,
+    key: String,
+    partition: Option<i32>,
+    max: Option<usize>,
+    decode_policy: Option<DecodePolicy>,
+) -> Result<CommandResult<KeyHistory>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let max = max.unwrap_or(100);
+    let policy = match decode_policy {
+        Some(policy) => policy,
+        None => service
+            .topic_settings(&topic)
+            .await
+            .decode_policy
+            .unwrap_or_default(),
+    };
+    Ok(service
+        .key_history(topic, key, partition, max, policy)
+        .await
+        .into())
+}
+
+/// Produce `count` numbered messages (at most 100,000) to one partition and check each is read
+/// back exactly once
+#[tauri::command]
+async fn reliability_test(
+    state: State<'_, AppState>,
+    topic: String,
+    count: usize,
+    partition: Option<i32>,
+) -> Result<CommandResult<ReliabilityReport>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let partition = partition.unwrap_or(0);
+    Ok(service
+        .reliability_test(topic, count, partition)
+        .await
+        .into())
+}
+
+/// Whether a topic is compacted; `None` when the broker config can't be read
+#[tauri::command]
+async fn is_topic_compacted(
+    state: State<'_, AppState>,
+    topic: String,
+) -> Result<CommandResult<Option<bool>>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.is_topic_compacted(topic).await.into())
+}
+
+/// Write the cluster's brokers, topics and partition assignments to a JSON file
+#[tauri::command]
+async fn export_cluster_topology(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<CommandResult<ExportResult>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.export_cluster_topology(path).await.into())
+}
+
+/// Check whether the current credentials may produce to a topic (writes one probe record)
+#[tauri::command]
+async fn can_produce(state: State<'_, AppState>, topic: String) -> Result<CommandResult<bool>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.can_produce(topic).await.into())
+}
+
+/// Check whether the current credentials may consume from a topic
+#[tauri::command]
+async fn can_consume(state: State<'_, AppState>, topic: String) -> Result<CommandResult<bool>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.can_consume(topic).await.into())
+}
+
+/// Begin a transaction (currently reported as unsupported by the Kafka client)
+#[tauri::command]
+async fn begin_transaction(
+    state: State<'_, AppState>,
+    transactional_id: String,
+) -> Result<CommandResult<()>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.begin_transaction(transactional_id).await.into())
+}
+
+/// Commit the open transaction
+#[tauri::command]
+async fn commit_transaction(state: State<'_, AppState>) -> Result<CommandResult<()>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.commit_transaction().await.into())
+}
+
+/// Abort the open transaction
+#[tauri::command]
+async fn abort_transaction(state: State<'_, AppState>) -> Result<CommandResult<()>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.abort_transaction().await.into())
+}
+
+/// Export messages from a partition of a topic (0 by default) to a JSONL file
+#[tauri::command]
+async fn export_messages(
+    state: State<'_, AppState>,
+    topic: String,
+    path: String,
+    offset: Option<i64>,
+    max_messages: Option<usize>,
+    options: Option<ExportOptions>,
+    partition: Option<i32>,
+) -> Result<CommandResult<ExportResult>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let partition = partition.unwrap_or(0);
+    let start_offset = offset.unwrap_or(0);
+    let max = max_messages.unwrap_or(10_000);
+    let options = options.unwrap_or_default();
+    Ok(service
+        .export_messages(topic, partition, start_offset, max, path, options)
+        .await
+        .into())
+}
+
+/// Produce the records of a JSONL export to a topic
+#[tauri::command]
+async fn import_messages(
+    state: State<'_, AppState>,
+    path: String,
+    topic: String,
+) -> Result<CommandResult<ImportResult>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    Ok(service.import_messages(path, topic).await.into())
+}
+
+/// Produce one record per row of a CSV file to a topic (the configured one by default), emitting
+/// `kafka://csv-progress` after each produce request
+#[tauri::command]
+async fn produce_from_csv(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+    topic: Option<String>,
+    key_column: Option<String>,
+    value_format: CsvValueFormat,
+) -> Result<CommandResult<CsvProduceResult>, ()> {
+    let service = state.kafka_service.lock().await.clone_service();
+    let topic = match topic {
+        Some(topic) => topic,
+        None => service.get_config().await.topic,
+    };
+    Ok(service
+        .produce_from_csv(path, topic, key_column, value_format, |progress| {
+            let _ = app.emit("kafka://csv-progress", progress);
+        })
+        .await
+        .into())
+}
+
+/// Start streaming log events as `kafka://log`, at most `max_per_second` a second (default 100,
+/// 0 for no cap)
+#[tauri::command]
+async fn subscribe_logs(
+    state: State<'_, AppState>,
+    min_level: Option<LogLevel>,
+    max_per_second: Option<u32>,
+) -> Result<(), ()> {
+    state
+        .log_stream
+        .subscribe(min_level.unwrap_or_default(), max_per_second.unwrap_or(100));
+    Ok(())
+}
+
+/// Stop streaming log events
+#[tauri::command]
+async fn unsubscribe_logs(state: State<'_, AppState>) -> Result<(), ()> {
+    state.log_stream.unsubscribe();
+    Ok(())
+}
+
+/// Forward log events from the tracing subscriber to the UI as `kafka://log`
+fn spawn_log_forwarding(app: AppHandle, stream: &LogStream) {
+    let mut events = stream.receiver();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    let _ = app.emit("kafka://log", event);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Keep the cached connection warm and forward reconnects and circuit breaker changes to the UI
+/// as `kafka://reconnect` and `kafka://circuit`
+fn spawn_keep_alive(app: AppHandle, service: KafkaService) {
+    let mut reconnects = service.subscribe_reconnects();
+    let reconnect_app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match reconnects.recv().await {
+                Ok(event) => {
+                    let _ = reconnect_app.emit("kafka://reconnect", event);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    let mut circuit_events = service.subscribe_circuit();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match circuit_events.recv().await {
+                Ok(event) => {
+                    let _ = app.emit("kafka://circuit", event);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let interval = service.get_config().await.keep_alive_interval_secs;
+            if interval == 0 {
+                // Disabled; check again later in case the setting changes
+                tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+                continue;
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
+            service.keep_alive().await;
+        }
+    });
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // Load config and create Kafka service
     let config = AppConfig::load();
-    let kafka_service = Arc::new(Mutex::new(KafkaService::new(config)));
-    
-    let app_state = AppState { kafka_service };
+    let positions = PositionStore::load();
+    let offline_queue = OfflineQueue::load();
+    let service = KafkaService::new(config, positions, offline_queue);
+    let background_service = service.clone_service();
+    let kafka_service = Arc::new(Mutex::new(service));
+
+    // Route tracing events (ours and rskafka's) to the log stream; nothing is forwarded until
+    // the UI subscribes
+    let log_stream = LogStream::new();
+    let _ = tracing::subscriber::set_global_default(LogForwarder::new(log_stream.clone()));
+    let forwarded_logs = log_stream.clone();
+
+    let app_state = AppState {
+        kafka_service,
+        log_stream,
+        tail: std::sync::Mutex::new(None),
+    };
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
         .manage(app_state)
+        .setup(move |app| {
+            spawn_keep_alive(app.handle().clone(), background_service);
+            spawn_log_forwarding(app.handle().clone(), &forwarded_logs);
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            // Don't leave a tail emitting to a window that is gone
+            if let WindowEvent::Destroyed = event {
+                window.state::<AppState>().stop_tailing();
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             send_kafka_message,
+            send_record,
+            send_kafka_messages_batch,
+            offline_queue_depth,
+            flush_offline_queue,
+            send_templated_message,
+            send_to_topics,
+            open_produce_session,
+            produce_chunk,
+            finish_produce,
+            app_info,
+            subscribe_logs,
+            unsubscribe_logs,
             get_kafka_config,
+            sample_config,
             save_kafka_config,
+            get_topic_overrides,
+            set_topic_override,
+            set_config_dir,
+            reset_config,
             test_kafka_connection,
+            rotate_credentials,
+            circuit_open,
+            ping_broker,
+            cancel_all_operations,
             create_kafka_topic,
             consume_kafka_messages,
+            get_partition_count,
+            consume_new_since_last,
+            consume_detailed,
+            consume_all_partitions,
+            get_message_at,
+            get_cluster_metadata,
+            check_topic_health,
+            list_kafka_topics,
+            sample_topic_stats,
+            detect_topic_format,
+            estimate_consume,
+            compare_topics,
+            latency_probe,
+            wait_for_messages,
+            start_tailing,
+            stop_tailing,
+            offsets_for_times,
+            produce_ordered,
+            verify_ordered,
+            key_history,
+            export_cluster_topology,
+            reliability_test,
+            is_topic_compacted,
+            can_produce,
+            can_consume,
+            unsupported_operations,
+            begin_transaction,
+            commit_transaction,
+            abort_transaction,
+            export_messages,
+            import_messages,
+            produce_from_csv,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata, Subscriber};
+
+/// Minimum severity of log events forwarded to the UI
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => Level::ERROR,
+            LogLevel::Warn => Level::WARN,
+            LogLevel::Info => Level::INFO,
+            LogLevel::Debug => Level::DEBUG,
+            LogLevel::Trace => Level::TRACE,
+        }
+    }
+}
+
+/// `Level` as a verbosity rank, 1 (error) to 5 (trace), for `LogStream::max_verbosity`
+fn verbosity(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 1,
+        Level::WARN => 2,
+        Level::INFO => 3,
+        Level::DEBUG => 4,
+        Level::TRACE => 5,
+    }
+}
+
+/// A log event from the app or rskafka, as emitted on `kafka://log`
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEvent {
+    pub level: String,
+    pub target: String,
+    pub message: String,
+    /// Milliseconds since the epoch
+    pub timestamp: i64,
+}
+
+/// The rate cap the UI subscribed with, and its bookkeeping; the level is `max_verbosity`
+struct LogFilter {
+    max_per_second: u32,
+    window_started: Instant,
+    sent_in_window: u32,
+    dropped: u64,
+}
+
+/// Shared switch between the tracing subscriber and the UI: events are only formatted and
+/// broadcast while someone is subscribed
+pub struct LogStream {
+    filter: Mutex<Option<LogFilter>>,
+    /// `verbosity` of the subscribed minimum level, 0 while unsubscribed, so `enabled` can
+    /// answer from every callsite without taking the lock
+    max_verbosity: AtomicU8,
+    events: broadcast::Sender<LogEvent>,
+}
+
+impl LogStream {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            filter: Mutex::new(None),
+            max_verbosity: AtomicU8::new(0),
+            events: broadcast::channel(256).0,
+        })
+    }
+
+    /// Start forwarding events at `min_level` or more severe, at most `max_per_second` a second
+    /// (0 lifts the cap). Events over the cap are dropped and counted in a summary event.
+    pub fn subscribe(&self, min_level: LogLevel, max_per_second: u32) {
+        let mut filter = self.filter.lock().unwrap();
+        *filter = Some(LogFilter {
+            max_per_second,
+            window_started: Instant::now(),
+            sent_in_window: 0,
+            dropped: 0,
+        });
+        self.max_verbosity
+            .store(verbosity(&min_level.into()), Ordering::Relaxed);
+    }
+
+    /// Stop forwarding events
+    pub fn unsubscribe(&self) {
+        let mut filter = self.filter.lock().unwrap();
+        filter.take();
+        self.max_verbosity.store(0, Ordering::Relaxed);
+    }
+
+    /// Receive forwarded events
+    pub fn receiver(&self) -> broadcast::Receiver<LogEvent> {
+        self.events.subscribe()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        verbosity(metadata.level()) <= self.max_verbosity.load(Ordering::Relaxed)
+    }
+
+    fn forward(&self, event: &Event<'_>) {
+        let metadata = event.metadata();
+        let mut dropped_summary = None;
+        {
+            let mut guard = self.filter.lock().unwrap();
+            let Some(filter) = guard.as_mut() else {
+                return;
+            };
+            if filter.max_per_second > 0 {
+                if filter.window_started.elapsed().as_secs() >= 1 {
+                    if filter.dropped > 0 {
+                        dropped_summary = Some(filter.dropped);
+                    }
+                    filter.window_started = Instant::now();
+                    filter.sent_in_window = 0;
+                    filter.dropped = 0;
+                }
+                if filter.sent_in_window >= filter.max_per_second {
+                    filter.dropped += 1;
+                    return;
+                }
+                filter.sent_in_window += 1;
+            }
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        // Nobody listening is fine
+        if let Some(dropped) = dropped_summary {
+            let _ = self.events.send(LogEvent {
+                level: Level::WARN.to_string(),
+                target: module_path!().to_string(),
+                message: format!("{} log events dropped by the rate cap", dropped),
+                timestamp,
+            });
+        }
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let _ = self.events.send(LogEvent {
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: visitor.message,
+            timestamp,
+        });
+    }
+}
+
+/// Collects an event's `message` field followed by its other fields as `name=value`
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let fields = std::mem::take(&mut self.message);
+            let _ = write!(self.message, "{:?}{}", value, fields);
+        } else {
+            let _ = write!(self.message, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Global tracing subscriber that forwards events to a `LogStream`. Spans are accepted but not
+/// tracked; only events are forwarded.
+pub struct LogForwarder {
+    stream: Arc<LogStream>,
+    next_span_id: AtomicU64,
+}
+
+impl LogForwarder {
+    pub fn new(stream: Arc<LogStream>) -> Self {
+        Self {
+            stream,
+            next_span_id: AtomicU64::new(1),
+        }
+    }
+}
+
+impl Subscriber for LogForwarder {
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        // The level filter changes at runtime, so callsites must be re-checked on each use
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.stream.enabled(metadata)
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(self.next_span_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        self.stream.forward(event);
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
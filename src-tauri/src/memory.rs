@@ -0,0 +1,259 @@
+use rskafka::record::{Record, RecordAndOffset};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::config::AppConfig;
+use crate::kafka::{
+    dedup_records_by_key, ConsumeOptions, FetchedRecords, KafkaError, PartitionMetadata,
+    TopicMetadata,
+};
+use crate::partitioner::partition_for_key;
+
+/// Topics and records kept in process memory for `in_memory` mode, so the UI and commands can
+/// be exercised without a broker. Nothing is persisted and records are never deleted, so the
+/// earliest offset of every partition is 0.
+#[derive(Debug, Default)]
+pub struct InMemoryBroker {
+    /// Records of each partition, indexed by offset
+    topics: HashMap<String, Vec<Vec<Record>>>,
+}
+
+impl InMemoryBroker {
+    /// Create a topic with `partitions` empty partitions
+    pub fn create_topic(&mut self, topic: &str, partitions: i32) -> Result<(), KafkaError> {
+        if self.topics.contains_key(topic) {
+            return Err(KafkaError::TopicCreateFailed(format!(
+                "Topic '{}' already exists",
+                topic
+            )));
+        }
+        self.topics.insert(
+            topic.to_string(),
+            vec![Vec::new(); partitions.max(1) as usize],
+        );
+        Ok(())
+    }
+
+    /// Number of partitions of `topic`
+    pub fn partition_count(&self, topic: &str) -> Result<i32, KafkaError> {
+        self.topics
+            .get(topic)
+            .map(|partitions| partitions.len() as i32)
+            .ok_or_else(|| unknown_topic(topic))
+    }
+
+    /// Append records to a partition, returning their offsets. Like a broker with
+    /// `auto.create.topics.enable`, a missing topic is created with one partition.
+    pub fn produce(
+        &mut self,
+        topic: &str,
+        partition: i32,
+        records: Vec<Record>,
+    ) -> Result<Vec<i64>, KafkaError> {
+        let partitions = self
+            .topics
+            .entry(topic.to_string())
+            .or_insert_with(|| vec![Vec::new()]);
+        let log = usize::try_from(partition)
+            .ok()
+            .and_then(|index| partitions.get_mut(index))
+            .ok_or_else(|| KafkaError::SendFailed(unknown_partition(topic, partition)))?;
+
+        let first = log.len() as i64;
+        let count = records.len() as i64;
+        log.extend(records);
+        Ok((first..first + count).collect())
+    }
+
+    /// The (earliest, latest) offsets of a partition
+    pub fn offset_range(&self, topic: &str, partition: i32) -> Result<(i64, i64), KafkaError> {
+        Ok((0, self.log(topic, partition)?.len() as i64))
+    }
+
+    /// Up to `max_records` records of a partition starting at `offset`
+    pub fn fetch(
+        &self,
+        topic: &str,
+        partition: i32,
+        offset: i64,
+        max_records: usize,
+    ) -> Result<Vec<RecordAndOffset>, KafkaError> {
+        let log = self.log(topic, partition)?;
+        let start = offset.clamp(0, log.len() as i64);
+        Ok(log
+            .iter()
+            .zip(0i64..)
+            .skip(start as usize)
+            .take(max_records)
+            .map(|(record, offset)| RecordAndOffset {
+                record: record.clone(),
+                offset,
+            })
+            .collect())
+    }
+
+    /// Offset of the first record of a partition whose timestamp is at or after `timestamp_ms`
+    pub fn offset_for_timestamp(
+        &self,
+        topic: &str,
+        partition: i32,
+        timestamp_ms: i64,
+    ) -> Result<Option<i64>, KafkaError> {
+        Ok(self
+            .log(topic, partition)?
+            .iter()
+            .position(|record| record.timestamp.timestamp_millis() >= timestamp_ms)
+            .map(|index| index as i64))
+    }
+
+    /// Metadata of every topic, sorted by name
+    pub fn topic_metadata(&self) -> Vec<TopicMetadata> {
+        let mut topics: Vec<TopicMetadata> = self
+            .topics
+            .iter()
+            .map(|(name, partitions)| TopicMetadata {
+                name: name.clone(),
+                partitions: (0..partitions.len() as i32)
+                    .map(|id| PartitionMetadata {
+                        id,
+                        leader: None,
+                        replicas: vec![],
+                        isr: vec![],
+                    })
+                    .collect(),
+            })
+            .collect();
+        topics.sort_by(|a, b| a.name.cmp(&b.name));
+        topics
+    }
+
+    fn log(&self, topic: &str, partition: i32) -> Result<&Vec<Record>, KafkaError> {
+        let partitions = self.topics.get(topic).ok_or_else(|| unknown_topic(topic))?;
+        usize::try_from(partition)
+            .ok()
+            .and_then(|index| partitions.get(index))
+            .ok_or_else(|| KafkaError::ConsumeFailed(unknown_partition(topic, partition)))
+    }
+}
+
+fn unknown_topic(topic: &str) -> KafkaError {
+    KafkaError::MetadataFailed(format!("Topic '{}' not found", topic))
+}
+
+fn unknown_partition(topic: &str, partition: i32) -> String {
+    format!("Topic '{}' has no partition {}", topic, partition)
+}
+
+/// `KafkaBackend` over an `InMemoryBroker`, used by `KafkaService` in `in_memory` mode and usable
+/// on its own as a mock backend. It reads the same config as the service it belongs to.
+#[derive(Clone)]
+pub struct InMemoryBackend {
+    config: Arc<Mutex<AppConfig>>,
+    broker: Arc<Mutex<InMemoryBroker>>,
+}
+
+impl InMemoryBackend {
+    pub fn new(config: Arc<Mutex<AppConfig>>) -> Self {
+        Self {
+            config,
+            broker: Arc::new(Mutex::new(InMemoryBroker::default())),
+        }
+    }
+
+    /// Lock the underlying broker
+    pub async fn broker(&self) -> MutexGuard<'_, InMemoryBroker> {
+        self.broker.lock().await
+    }
+
+    pub async fn config(&self) -> AppConfig {
+        self.config.lock().await.clone()
+    }
+
+    /// Store a record on `topic`, on `partition` or else picking one from its key like the real
+    /// producer, returning the partition and offset it was written to
+    pub async fn store(
+        &self,
+        topic: &str,
+        record: Record,
+        partition: Option<i32>,
+    ) -> Result<(i32, i64), KafkaError> {
+        let default_partition = self.config().await.topic_settings(topic).default_partition;
+        let mut broker = self.broker.lock().await;
+        let partition = match (partition, &record.key) {
+            (Some(partition), _) => partition,
+            (None, Some(key)) => partition_for_key(key, broker.partition_count(topic).unwrap_or(1)),
+            (None, None) => default_partition.unwrap_or(0),
+        };
+        let offsets = broker.produce(topic, partition, vec![record])?;
+        Ok((partition, offsets[0]))
+    }
+
+    /// Read up to `max_messages` records of a partition, honouring `offset_reset`,
+    /// `max_total_bytes` and `key_filter` like a broker fetch. `deadline_ms` is ignored since
+    /// reads never wait.
+    pub async fn fetch_records(
+        &self,
+        topic: &str,
+        partition: i32,
+        offset: i64,
+        max_messages: usize,
+        dedup_by_key: bool,
+        options: &ConsumeOptions,
+    ) -> Result<FetchedRecords, KafkaError> {
+        let broker = self.broker.lock().await;
+        let (earliest, latest) = broker.offset_range(topic, partition)?;
+        let offset = match options.from_timestamp_ms {
+            Some(timestamp_ms) => {
+                match broker.offset_for_timestamp(topic, partition, timestamp_ms)? {
+                    Some(offset) => offset,
+                    None => {
+                        return Ok(FetchedRecords {
+                            records: vec![],
+                            truncated: false,
+                            next_offset: latest,
+                        })
+                    }
+                }
+            }
+            None => offset,
+        };
+        let mut next_offset = options.offset_reset.resolve(offset, earliest, latest)?;
+        let mut records = Vec::new();
+        let mut total_bytes = 0;
+        // Read past non-matching keys so `max_messages` counts matches
+        let fetch_limit = if options.key_filter.is_some() {
+            usize::MAX
+        } else {
+            max_messages
+        };
+        for record in broker.fetch(topic, partition, next_offset, fetch_limit)? {
+            if records.len() >= max_messages {
+                break;
+            }
+            if !options.key_matches(record.record.key.as_deref()) {
+                next_offset = record.offset + 1;
+                continue;
+            }
+            let size = record.record.value.as_ref().map_or(0, Vec::len);
+            if options
+                .max_total_bytes
+                .is_some_and(|cap| total_bytes + size > cap)
+            {
+                break;
+            }
+            total_bytes += size;
+            next_offset = record.offset + 1;
+            records.push(record);
+        }
+
+        if dedup_by_key {
+            records = dedup_records_by_key(records, options.include_tombstones);
+        }
+        Ok(FetchedRecords {
+            records,
+            truncated: false,
+            next_offset,
+        })
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+use crate::kafka::KafkaError;
+
+/// Long-running operations in flight (consumes, exports, imports, latency probes), so they can
+/// be cancelled together
+#[derive(Default)]
+pub struct OperationRegistry {
+    next_id: AtomicU64,
+    operations: Mutex<HashMap<u64, watch::Sender<bool>>>,
+}
+
+/// Registration of one operation; dropping it (when the operation ends) unregisters it
+pub struct OperationGuard {
+    id: u64,
+    registry: Arc<OperationRegistry>,
+    cancelled: watch::Receiver<bool>,
+}
+
+impl OperationRegistry {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Register an operation that should stop once cancelled
+    pub fn register(self: &Arc<Self>) -> OperationGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, cancelled) = watch::channel(false);
+        self.operations.lock().unwrap().insert(id, sender);
+        OperationGuard {
+            id,
+            registry: Arc::clone(self),
+            cancelled,
+        }
+    }
+
+    /// Run `future` as a registered operation, failing with `Cancelled` if it is cancelled first
+    pub async fn run<T, F>(self: &Arc<Self>, operation: &str, future: F) -> Result<T, KafkaError>
+    where
+        F: Future<Output = Result<T, KafkaError>>,
+    {
+        let mut guard = self.register();
+        tokio::select! {
+            result = future => result,
+            _ = guard.cancelled() => Err(KafkaError::Cancelled(operation.to_string())),
+        }
+    }
+
+    /// Signal every registered operation to stop and forget them, returning how many there were.
+    ///
+    /// Taking the whole map under the lock means an operation finishing at the same time is
+    /// either cancelled here or already unregistered, never both; its guard then finds nothing
+    /// left to remove.
+    pub fn cancel_all(&self) -> usize {
+        let operations = std::mem::take(&mut *self.operations.lock().unwrap());
+        for sender in operations.values() {
+            // The operation may have just finished and dropped its receiver; that's fine
+            let _ = sender.send(true);
+        }
+        operations.len()
+    }
+}
+
+impl OperationGuard {
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancelled.borrow()
+    }
+
+    /// Resolve once the operation is cancelled; never resolves otherwise
+    pub async fn cancelled(&mut self) {
+        if self
+            .cancelled
+            .wait_for(|cancelled| *cancelled)
+            .await
+            .is_err()
+        {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        self.registry.operations.lock().unwrap().remove(&self.id);
+    }
+}
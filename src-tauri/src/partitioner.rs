@@ -0,0 +1,105 @@
+/// Murmur2 hash as implemented by the Java client (`org.apache.kafka.common.utils.Utils.murmur2`)
+/// and librdkafka's `murmur2` partitioners.
+pub fn murmur2(data: &[u8]) -> i32 {
+    const SEED: u32 = 0x9747_b28c;
+    const M: u32 = 0x5bd1_e995;
+    const R: u32 = 24;
+
+    let length = data.len();
+    let mut h: u32 = SEED ^ length as u32;
+
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M);
+        h ^= k;
+    }
+
+    // Mix in the trailing bytes (the Java switch falls through 3 -> 2 -> 1)
+    let tail = chunks.remainder();
+    if tail.len() >= 3 {
+        h ^= (tail[2] as u32) << 16;
+    }
+    if tail.len() >= 2 {
+        h ^= (tail[1] as u32) << 8;
+    }
+    if !tail.is_empty() {
+        h ^= tail[0] as u32;
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^= h >> 15;
+
+    h as i32
+}
+
+/// Pick the partition for a record key the same way the Java client's default partitioner does:
+/// `(murmur2(key) & 0x7fffffff) % partitions`
+pub fn partition_for_key(key: &[u8], partitions: i32) -> i32 {
+    if partitions <= 0 {
+        return 0;
+    }
+    (murmur2(key) & 0x7fff_ffff) % partitions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn murmur2_matches_java_client() {
+        // Expected values from the Java client's `UtilsTest.testMurmur2`
+        let cases: [(&[u8], i32); 7] = [
+            (b"", 275_646_681),
+            (b"21", -973_932_308),
+            (b"foobar", -790_332_482),
+            (b"a-little-bit-long-string", -985_981_536),
+            (b"a-little-bit-longer-string", -1_486_304_829),
+            (
+                b"lkjh234lh9fiuh90y23oiuhsafujhadof229phr9h19h89h8",
+                -58_897_971,
+            ),
+            (b"abc", 479_470_107),
+        ];
+        for (key, expected) in cases {
+            assert_eq!(
+                murmur2(key),
+                expected,
+                "key {:?}",
+                String::from_utf8_lossy(key)
+            );
+        }
+    }
+
+    #[test]
+    fn murmur2_hashes_non_ascii_keys_by_utf8_bytes() {
+        assert_eq!(murmur2("café".as_bytes()), -1_358_007_374);
+        assert_eq!(murmur2("日本".as_bytes()), -700_811_021);
+    }
+
+    #[test]
+    fn partition_for_key_matches_java_default_partitioner() {
+        let cases: [(&str, [i32; 4]); 5] = [
+            ("", [0, 3, 1, 9]),
+            ("a", [1, 4, 4, 4]),
+            ("hello", [0, 3, 9, 9]),
+            ("order-12345", [1, 1, 9, 1]),
+            ("café", [0, 0, 4, 6]),
+        ];
+        for (key, expected) in cases {
+            let partitions = [3, 6, 10, 12].map(|count| partition_for_key(key.as_bytes(), count));
+            assert_eq!(partitions, expected, "key {:?}", key);
+        }
+    }
+
+    #[test]
+    fn partition_for_key_with_no_partitions_is_zero() {
+        assert_eq!(partition_for_key(b"hello", 0), 0);
+        assert_eq!(partition_for_key(b"hello", -1), 0);
+    }
+}
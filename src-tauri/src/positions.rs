@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::{app_config_dir, remove_if_exists, write_atomically, ConfigError};
+
+/// Per-topic consume positions remembered between sessions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PositionStore {
+    /// Next offset to read for each topic (partition 0)
+    #[serde(default)]
+    next_offsets: HashMap<String, i64>,
+}
+
+impl PositionStore {
+    /// Get the positions file path in the app data directory
+    fn positions_path() -> Option<PathBuf> {
+        app_config_dir().map(|dir| dir.join("positions.json"))
+    }
+
+    /// Load positions from disk, or return an empty store if not found
+    pub fn load() -> Self {
+        Self::positions_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save positions to disk
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let path = Self::positions_path().ok_or(ConfigError::NoConfigDir)?;
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| ConfigError::SerializeError(e.to_string()))?;
+
+        write_atomically(&path, &content, false)
+    }
+
+    /// Delete the saved positions file
+    pub fn delete_saved() -> Result<(), ConfigError> {
+        let path = Self::positions_path().ok_or(ConfigError::NoConfigDir)?;
+        remove_if_exists(&path)
+    }
+
+    /// Next offset to read for a topic, if one was stored
+    pub fn next_offset(&self, topic: &str) -> Option<i64> {
+        self.next_offsets.get(topic).copied()
+    }
+
+    /// Remember the next offset to read for a topic
+    pub fn set_next_offset(&mut self, topic: &str, offset: i64) {
+        self.next_offsets.insert(topic.to_string(), offset);
+    }
+}
@@ -0,0 +1,86 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::kafka::KafkaError;
+
+/// SASL username and password kept in a file of their own (`sasl_credentials_path`), so
+/// `config.json` can be shared without secrets
+#[derive(Clone, Deserialize)]
+pub struct SaslFileCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// Last file read, reused until the file's modification time changes
+struct CachedCredentials {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    credentials: SaslFileCredentials,
+}
+
+static CACHE: Mutex<Option<CachedCredentials>> = Mutex::new(None);
+
+/// Modification time of `path`, if it can be read
+pub fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Read `{ username, password }` from a `.toml` file, or JSON for any other extension.
+///
+/// The parsed file is cached and re-read once its modification time changes, so rotated
+/// credentials are picked up by the next connection. The password is never included in errors.
+pub fn load_sasl_credentials(path: &Path) -> Result<SaslFileCredentials, KafkaError> {
+    let modified = modified_time(path);
+    let mut cache = CACHE.lock().unwrap();
+    if let Some(cached) = cache.as_ref() {
+        if cached.path == path && modified.is_some() && cached.modified == modified {
+            return Ok(cached.credentials.clone());
+        }
+    }
+
+    let invalid = |reason: String| {
+        KafkaError::InvalidConfig(format!(
+            "SASL credentials file '{}' {}",
+            path.display(),
+            reason
+        ))
+    };
+    let content =
+        fs::read_to_string(path).map_err(|e| invalid(format!("could not be read: {}", e)))?;
+    let is_toml = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("toml"));
+    // Parser messages can quote values, so only the location is reported
+    let credentials: SaslFileCredentials = if is_toml {
+        toml::from_str(&content).map_err(|e| {
+            let location = e.span().map(|span| format!(" (at byte {})", span.start));
+            invalid(format!(
+                "is not valid TOML or lacks username/password{}",
+                location.unwrap_or_default()
+            ))
+        })?
+    } else {
+        serde_json::from_str(&content).map_err(|e| {
+            invalid(format!(
+                "is not valid JSON or lacks username/password (line {}, column {})",
+                e.line(),
+                e.column()
+            ))
+        })?
+    };
+    if credentials.username.is_empty() {
+        return Err(invalid("has an empty username".to_string()));
+    }
+
+    *cache = Some(CachedCredentials {
+        path: path.to_path_buf(),
+        modified,
+        credentials: credentials.clone(),
+    });
+    Ok(credentials)
+}
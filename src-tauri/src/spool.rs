@@ -0,0 +1,91 @@
+use rskafka::record::Record;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::{app_config_dir, write_atomically, CompressionCodec, ConfigError};
+use crate::export::ExportedRecord;
+
+/// A message that couldn't be sent because the broker was unreachable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpooledMessage {
+    pub topic: String,
+    /// The record as it would have been produced; `partition` and `offset` are unused
+    pub record: ExportedRecord,
+    /// Partition chosen at send time (an explicit partition, or the default for unkeyed
+    /// records); keyed records without one are hashed again on replay
+    #[serde(default)]
+    pub partition: Option<i32>,
+    /// Codec the send would have used
+    #[serde(default)]
+    pub compression: Option<CompressionCodec>,
+    /// Idempotency key of the original send, recorded as acknowledged once replayed
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+/// Messages waiting for the broker to come back, oldest first, persisted between sessions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OfflineQueue {
+    #[serde(default)]
+    messages: VecDeque<SpooledMessage>,
+}
+
+impl OfflineQueue {
+    /// Get the spool file path in the app data directory
+    fn queue_path() -> Option<PathBuf> {
+        app_config_dir().map(|dir| dir.join("offline_queue.json"))
+    }
+
+    /// Load the queue from disk, or return an empty queue if not found
+    pub fn load() -> Self {
+        Self::queue_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save the queue to disk
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let path = Self::queue_path().ok_or(ConfigError::NoConfigDir)?;
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| ConfigError::SerializeError(e.to_string()))?;
+
+        write_atomically(&path, &content, false)
+    }
+
+    /// Append a record for `topic`, with the partition and codec it should be replayed to
+    pub fn push(
+        &mut self,
+        topic: &str,
+        record: &Record,
+        partition: Option<i32>,
+        compression: CompressionCodec,
+        idempotency_key: Option<String>,
+    ) {
+        self.messages.push_back(SpooledMessage {
+            topic: topic.to_string(),
+            record: ExportedRecord::new(record, 0, -1),
+            partition,
+            compression: Some(compression),
+            idempotency_key,
+        });
+    }
+
+    /// Oldest waiting message
+    pub fn front(&self) -> Option<&SpooledMessage> {
+        self.messages.front()
+    }
+
+    /// Drop the oldest waiting message once it has been produced
+    pub fn pop_front(&mut self) {
+        self.messages.pop_front();
+    }
+
+    /// Number of waiting messages
+    pub fn depth(&self) -> usize {
+        self.messages.len()
+    }
+}
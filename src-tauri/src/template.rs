@@ -0,0 +1,184 @@
+use chrono::{SecondsFormat, Utc};
+use uuid::Uuid;
+
+/// A message template with `{{placeholder}}`s expanded per message:
+///
+/// - `{{uuid}}`: a random v4 UUID
+/// - `{{timestamp}}`: milliseconds since the epoch; `{{timestamp_iso}}` for RFC 3339 in UTC
+/// - `{{seq}}`: the message's number in the run, counting from 0
+/// - `{{random_int(min,max)}}`: a random integer in `min..=max`
+///
+/// Whitespace inside the braces is ignored.
+pub struct MessageTemplate {
+    parts: Vec<Part>,
+}
+
+enum Part {
+    Literal(String),
+    Uuid,
+    Timestamp,
+    TimestampIso,
+    Seq,
+    RandomInt { min: i64, max: i64 },
+}
+
+impl MessageTemplate {
+    /// Parse `template`, failing on unknown placeholders, bad arguments and unclosed braces
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let after = &rest[start + 2..];
+            let end = after
+                .find("}}")
+                .ok_or_else(|| format!("Unclosed placeholder '{{{{{}'", after))?;
+            parts.push(Part::parse(after[..end].trim())?);
+            rest = &after[end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(MessageTemplate { parts })
+    }
+
+    /// Expand the template for message number `seq`
+    pub fn render(&self, seq: u64) -> String {
+        let mut message = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => message.push_str(text),
+                Part::Uuid => message.push_str(&Uuid::new_v4().to_string()),
+                Part::Timestamp => message.push_str(&Utc::now().timestamp_millis().to_string()),
+                Part::TimestampIso => {
+                    message.push_str(&Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true))
+                }
+                Part::Seq => message.push_str(&seq.to_string()),
+                Part::RandomInt { min, max } => {
+                    // A v4 UUID carries 122 random bits; there is no RNG dependency otherwise
+                    let span = (*max as i128 - *min as i128) as u128 + 1;
+                    let value = *min as i128 + (Uuid::new_v4().as_u128() % span) as i128;
+                    message.push_str(&value.to_string());
+                }
+            }
+        }
+        message
+    }
+}
+
+impl Part {
+    fn parse(placeholder: &str) -> Result<Self, String> {
+        match placeholder {
+            "uuid" => return Ok(Part::Uuid),
+            "timestamp" => return Ok(Part::Timestamp),
+            "timestamp_iso" => return Ok(Part::TimestampIso),
+            "seq" => return Ok(Part::Seq),
+            _ => {}
+        }
+
+        let args = placeholder
+            .strip_prefix("random_int")
+            .map(str::trim_start)
+            .and_then(|rest| rest.strip_prefix('('))
+            .and_then(|rest| rest.strip_suffix(')'))
+            .ok_or_else(|| format!("Unknown placeholder '{{{{{}}}}}'", placeholder))?;
+        let bounds = args
+            .split(',')
+            .map(|bound| bound.trim().parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()
+            .ok()
+            .filter(|bounds| bounds.len() == 2)
+            .ok_or_else(|| format!("random_int needs two integer bounds, got '{}'", args))?;
+        let (min, max) = (bounds[0], bounds[1]);
+        if min > max {
+            return Err(format!("random_int bounds are reversed: {} > {}", min, max));
+        }
+        Ok(Part::RandomInt { min, max })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_literals_and_seq() {
+        let template = MessageTemplate::parse("order-{{ seq }} done").unwrap();
+        assert_eq!(template.render(0), "order-0 done");
+        assert_eq!(template.render(41), "order-41 done");
+        assert_eq!(MessageTemplate::parse("").unwrap().render(3), "");
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders() {
+        let error = MessageTemplate::parse("{{nope}}").err().unwrap();
+        assert!(
+            error.contains("Unknown placeholder '{{nope}}'"),
+            "{}",
+            error
+        );
+        assert!(MessageTemplate::parse("{{random_int}}").is_err());
+        assert!(MessageTemplate::parse("{{random_int(1)}}").is_err());
+        assert!(MessageTemplate::parse("{{random_int(1,2,3)}}").is_err());
+        assert!(MessageTemplate::parse("{{random_int(a,b)}}").is_err());
+    }
+
+    #[test]
+    fn rejects_unclosed_braces() {
+        let error = MessageTemplate::parse("id={{uuid").err().unwrap();
+        assert!(error.starts_with("Unclosed placeholder"), "{}", error);
+        assert!(MessageTemplate::parse("{{seq}} and {{").is_err());
+        // A lone closing pair is just text
+        assert_eq!(MessageTemplate::parse("a}}b").unwrap().render(0), "a}}b");
+    }
+
+    #[test]
+    fn rejects_reversed_bounds() {
+        let error = MessageTemplate::parse("{{random_int(5,1)}}").err().unwrap();
+        assert!(error.contains("reversed"), "{}", error);
+    }
+
+    #[test]
+    fn random_int_stays_in_bounds() {
+        let single = MessageTemplate::parse("{{random_int( 7 , 7 )}}").unwrap();
+        assert_eq!(single.render(0), "7");
+
+        let negative = MessageTemplate::parse("{{random_int(-3,3)}}").unwrap();
+        for seq in 0..100 {
+            let value: i64 = negative.render(seq).parse().unwrap();
+            assert!((-3..=3).contains(&value));
+        }
+    }
+
+    #[test]
+    fn random_int_handles_i64_extremes() {
+        let template = format!("{{{{random_int({},{})}}}}", i64::MIN, i64::MAX);
+        let full_range = MessageTemplate::parse(&template).unwrap();
+        for seq in 0..100 {
+            assert!(full_range.render(seq).parse::<i64>().is_ok());
+        }
+
+        let template = format!("{{{{random_int({},{})}}}}", i64::MIN, i64::MIN);
+        assert_eq!(
+            MessageTemplate::parse(&template).unwrap().render(0),
+            i64::MIN.to_string()
+        );
+        let template = format!("{{{{random_int({},{})}}}}", i64::MAX, i64::MAX);
+        assert_eq!(
+            MessageTemplate::parse(&template).unwrap().render(0),
+            i64::MAX.to_string()
+        );
+    }
+
+    #[test]
+    fn uuid_and_timestamps_expand() {
+        let template = MessageTemplate::parse("{{uuid}}|{{timestamp}}|{{timestamp_iso}}").unwrap();
+        let rendered = template.render(0);
+        let fields: Vec<&str> = rendered.split('|').collect();
+        assert!(Uuid::parse_str(fields[0]).is_ok());
+        assert!(fields[1].parse::<i64>().is_ok());
+        assert!(chrono::DateTime::parse_from_rfc3339(fields[2]).is_ok());
+    }
+}
@@ -30,7 +30,8 @@ export class AppComponent implements OnInit, OnDestroy {
     ssl_ca_cert_path: '',
     ssl_client_cert_path: '',
     ssl_client_key_path: '',
-    ssl_skip_verification: false
+    ssl_skip_verification: false,
+    compression: 'NoCompression'
   };
 
   // Message input
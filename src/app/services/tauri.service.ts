@@ -20,6 +20,9 @@ export type SecurityProtocol = 'Plaintext' | 'Ssl' | 'SaslPlaintext' | 'SaslSsl'
 /** SASL mechanism options */
 export type SaslMechanism = 'Plain' | 'ScramSha256' | 'ScramSha512';
 
+/** Compression codec for produced record batches */
+export type CompressionCodec = 'NoCompression' | 'Gzip' | 'Snappy' | 'Lz4' | 'Zstd';
+
 /** Kafka configuration */
 export interface KafkaConfig {
   broker: string;
@@ -33,6 +36,7 @@ export interface KafkaConfig {
   ssl_client_cert_path: string;
   ssl_client_key_path: string;
   ssl_skip_verification: boolean;
+  compression: CompressionCodec;
 }
 
 /** Result of a message send operation */
@@ -40,6 +44,11 @@ export interface SendResult {
   success: boolean;
   message: string;
   timestamp: number;
+  /** Compression codec used for the produce request, e.g. 'Gzip' */
+  compression: string;
+  /** Partition and offset the record was written to, when known */
+  partition: number | null;
+  offset: number | null;
 }
 
 /** Command result wrapper from Rust */
@@ -64,24 +73,58 @@ export interface TopicCreateResult {
   topic: string;
 }
 
+/** A consumed header value: UTF-8 text in `value`, or base64 in `value_base64` for binary values */
+export interface HeaderValue {
+  value: string | null;
+  value_base64: string | null;
+}
+
 /** A consumed message from Kafka */
 export interface ConsumedMessage {
+  /** Partition the message was read from */
+  partition: number;
   offset: number;
   key: string | null;
   value: string | null;
+  /** Record headers by name; empty when the record has none */
+  headers: Record<string, HeaderValue>;
   timestamp: number;
 }
 
+/** Messages merged from every partition of a topic */
+export interface MultiPartitionConsumeResult {
+  /** Sorted by timestamp */
+  messages: ConsumedMessage[];
+  /** Offset to resume from on each partition */
+  next_offsets: Record<number, number>;
+  truncated: boolean;
+}
+
 @Injectable({
   providedIn: 'root'
 })
 export class TauriService {
-  
+  /** Removes the `startTailing` event listeners */
+  private unlistenTail?: () => void;
+
   /**
-   * Send a message to Kafka
+   * Send a message to Kafka, optionally keyed (an empty string is a real, zero-length key) or to
+   * a specific partition, with optional record headers; `repeat` sends it that many times
    */
-  async sendMessage(message: string): Promise<SendResult> {
-    const result = await tauriInvoke<CommandResult<SendResult>>('send_kafka_message', { message });
+  async sendMessage(
+    message: string,
+    key?: string,
+    partition?: number,
+    headers?: Record<string, string>,
+    repeat?: number
+  ): Promise<SendResult> {
+    const result = await tauriInvoke<CommandResult<SendResult>>('send_kafka_message', {
+      message,
+      key,
+      partition,
+      headers,
+      repeat
+    });
     
     if (result.type === 'Ok') {
       return result.data as SendResult;
@@ -139,13 +182,26 @@ export class TauriService {
   }
 
   /**
-   * Consume messages from a Kafka topic
+   * Consume messages from a Kafka topic, starting at `fromTimestampMs` instead of `offset` when
+   * given, and optionally only those whose key equals (or with `keySubstring`, contains) `keyFilter`
    */
-  async consumeMessages(topic: string, offset: number = 0, maxMessages: number = 50): Promise<ConsumedMessage[]> {
+  async consumeMessages(
+    topic: string,
+    offset: number = 0,
+    maxMessages: number = 50,
+    fromTimestampMs?: number,
+    keyFilter?: string,
+    keySubstring: boolean = false
+  ): Promise<ConsumedMessage[]> {
     const result = await tauriInvoke<CommandResult<ConsumedMessage[]>>('consume_kafka_messages', {
       topic,
       offset,
       maxMessages,
+      options: {
+        from_timestamp_ms: fromTimestampMs ?? null,
+        key_filter: keyFilter ?? null,
+        key_filter_substring: keySubstring,
+      },
     });
 
     if (result.type === 'Ok') {
@@ -155,6 +211,68 @@ export class TauriService {
     }
   }
 
+  /**
+   * Consume up to `maxMessages` from each partition of a topic, merged by timestamp
+   */
+  async consumeAllPartitions(
+    topic: string,
+    offset: number = 0,
+    maxMessages: number = 50
+  ): Promise<MultiPartitionConsumeResult> {
+    const result = await tauriInvoke<CommandResult<MultiPartitionConsumeResult>>('consume_all_partitions', {
+      topic,
+      offset,
+      maxMessages,
+    });
+
+    if (result.type === 'Ok') {
+      return result.data as MultiPartitionConsumeResult;
+    } else {
+      throw new Error(result.data as string);
+    }
+  }
+
+  /**
+   * Follow a topic from its latest offset, calling `onMessage` for each new message until
+   * `stopTailing` is called or the tail fails with the error passed to `onError`
+   */
+  async startTailing(
+    onMessage: (message: ConsumedMessage) => void,
+    topic?: string,
+    onError?: (error: string) => void
+  ): Promise<void> {
+    if (!isTauri()) {
+      throw new Error('Not running inside Tauri. Use "npx tauri dev" to launch the app.');
+    }
+    const { listen } = await import('@tauri-apps/api/event');
+    this.unlistenTail?.();
+    const unlistenMessage = await listen<ConsumedMessage>('kafka://message', (event) => onMessage(event.payload));
+    const unlistenError = await listen<string>('kafka://tail-error', (event) => {
+      this.unlistenTail?.();
+      onError?.(event.payload);
+    });
+    this.unlistenTail = () => {
+      unlistenMessage();
+      unlistenError();
+      this.unlistenTail = undefined;
+    };
+
+    const result = await tauriInvoke<CommandResult<null>>('start_tailing', { topic: topic ?? null });
+    if (result.type !== 'Ok') {
+      this.unlistenTail();
+      throw new Error(result.data as string);
+    }
+  }
+
+  /**
+   * Stop the tail started by `startTailing`, returning whether one was running
+   */
+  async stopTailing(): Promise<boolean> {
+    const result = await tauriInvoke<CommandResult<boolean>>('stop_tailing');
+    this.unlistenTail?.();
+    return result.type === 'Ok' && (result.data as boolean);
+  }
+
   /**
    * Generate a unique ID for message entries
    */